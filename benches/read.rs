@@ -0,0 +1,189 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_bser::de::{BufIoRead, Deserializer, IoRead};
+use serde_bser::ser::Serializer;
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    name: String,
+    size: i64,
+    exists: bool,
+    mtime: i64,
+}
+
+/// Write a watchman-like query result to a temporary file, so that reads go
+/// through real system calls.
+fn payload() -> PathBuf {
+    let files: Vec<FileEntry> = (0..1000)
+        .map(|i| FileEntry {
+            name: format!("src/some/directory/file{}.rs", i),
+            size: i * 1024,
+            exists: i % 7 != 0,
+            mtime: 1_500_000_000 + i,
+        })
+        .collect();
+
+    let path = std::env::temp_dir().join("serde-bser-read-bench.bser");
+    fs::write(&path, serde_bser::ser::to_vec(&files).unwrap()).unwrap();
+    path
+}
+
+fn bench_read(c: &mut Criterion) {
+    let path = payload();
+    let mut group = c.benchmark_group("read_file");
+
+    group.bench_function("IoRead", |b| {
+        b.iter(|| {
+            let file = File::open(&path).unwrap();
+            let mut de = Deserializer::native(IoRead::new(file));
+            Vec::<FileEntry>::deserialize(&mut de).unwrap()
+        })
+    });
+
+    group.bench_function("BufIoRead", |b| {
+        b.iter(|| {
+            let file = File::open(&path).unwrap();
+            let mut de = Deserializer::native(BufIoRead::new(file));
+            Vec::<FileEntry>::deserialize(&mut de).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+/// A row of a query result which borrows its name from the input.
+#[derive(Serialize, Deserialize)]
+struct BorrowedEntry<'a> {
+    name: &'a str,
+    size: i64,
+    exists: bool,
+    mtime: i64,
+}
+
+fn bench_read_templated(c: &mut Criterion) {
+    let names: Vec<String> = (0..10_000)
+        .map(|i| format!("src/some/directory/file{}.rs", i))
+        .collect();
+    let files: Vec<BorrowedEntry> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| BorrowedEntry {
+            name,
+            size: i as i64 * 1024,
+            exists: i % 7 != 0,
+            mtime: 1_500_000_000 + i as i64,
+        })
+        .collect();
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    files.serialize(&mut ser).unwrap();
+    let bytes = ser.into_inner();
+
+    let mut group = c.benchmark_group("read_templated");
+
+    group.bench_function("borrowed", |b| {
+        b.iter(|| serde_bser::de::from_slice::<Vec<BorrowedEntry>>(&bytes).unwrap())
+    });
+
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let mut de = Deserializer::from_reader(&bytes[..]);
+            Vec::<FileEntry>::deserialize(&mut de).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+macro_rules! wide_struct {
+    ($($field:ident)*) => {
+        /// A struct with many fields, as some Watchman queries return.
+        #[derive(Serialize, Deserialize, Default)]
+        struct Wide {
+            $($field: i64,)*
+        }
+    };
+}
+
+wide_struct! {
+    name_hash size mode uid gid mtime mtime_ms mtime_us mtime_ns mtime_f
+    ctime ctime_ms ctime_us ctime_ns ctime_f ino dev nlink new_file exists
+    cclock oclock content_sha1hex symlink_target_len type_code
+}
+
+fn bench_read_wide(c: &mut Criterion) {
+    let rows: Vec<Wide> = (0..10_000).map(|_| Wide::default()).collect();
+    let objects = serde_bser::ser::to_vec(&rows).unwrap();
+    // The same rows without keys, for comparison.
+    let arrays = serde_bser::ser::to_vec(&vec![[0i64; 25]; 10_000]).unwrap();
+
+    let mut group = c.benchmark_group("read_wide");
+
+    group.bench_function("objects", |b| {
+        b.iter(|| serde_bser::de::from_slice::<Vec<Wide>>(&objects).unwrap())
+    });
+
+    group.bench_function("arrays", |b| {
+        b.iter(|| serde_bser::de::from_slice::<Vec<Wide>>(&arrays).unwrap())
+    });
+
+    group.finish();
+}
+
+/// Five of the fifty columns written by `bench_read_columns`.
+#[derive(Deserialize)]
+struct Narrow {
+    col00: i64,
+    col10: i64,
+    col20: i64,
+    col30: i64,
+    col40: i64,
+}
+
+impl Narrow {
+    fn sum(&self) -> i64 {
+        self.col00 + self.col10 + self.col20 + self.col30 + self.col40
+    }
+}
+
+fn bench_read_columns(c: &mut Criterion) {
+    let row: BTreeMap<String, i64> = (0..50).map(|i| (format!("col{:02}", i), i)).collect();
+    let rows = vec![row; 10_000];
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    rows.serialize(&mut ser).unwrap();
+    let bytes = ser.into_inner();
+
+    let mut group = c.benchmark_group("read_columns");
+
+    group.bench_function("narrow", |b| {
+        b.iter(|| {
+            let rows = serde_bser::de::from_slice::<Vec<Narrow>>(&bytes).unwrap();
+            rows.iter().map(Narrow::sum).sum::<i64>()
+        })
+    });
+
+    group.bench_function("narrow_skipping", |b| {
+        b.iter(|| {
+            let mut de = Deserializer::from_slice(&bytes);
+            de.set_skip_unused_columns(true);
+            let rows = Vec::<Narrow>::deserialize(&mut de).unwrap();
+            rows.iter().map(Narrow::sum).sum::<i64>()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_read,
+    bench_read_templated,
+    bench_read_wide,
+    bench_read_columns
+);
+criterion_main!(benches);
@@ -0,0 +1,169 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_bser::ser::{to_vec, to_vec_exact, to_writer, to_writer_pdu, PduWriter};
+use serde_derive::Serialize;
+
+use std::io;
+
+#[derive(Serialize)]
+struct Notification {
+    subscription: String,
+    clock: String,
+    files: Vec<String>,
+}
+
+fn notifications() -> Vec<Notification> {
+    (0..100)
+        .map(|i| Notification {
+            subscription: "my-subscription".to_owned(),
+            clock: format!("c:1500000000:{}", i),
+            files: (0..i).map(|j| format!("src/file{}.rs", j)).collect(),
+        })
+        .collect()
+}
+
+fn bench_write(c: &mut Criterion) {
+    let notifications = notifications();
+    let mut group = c.benchmark_group("write_pdus");
+
+    group.bench_function("to_writer_pdu", |b| {
+        b.iter(|| {
+            for n in &notifications {
+                to_writer_pdu(io::sink(), n).unwrap();
+            }
+        })
+    });
+
+    group.bench_function("PduWriter", |b| {
+        let mut writer = PduWriter::new();
+        b.iter(|| {
+            for n in &notifications {
+                writer.write_pdu(io::sink(), n).unwrap();
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Serializes a byte string with `serialize_bytes`.
+struct Blob(Vec<u8>);
+
+impl serde::ser::Serialize for Blob {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&self.0)
+    }
+}
+
+fn bench_write_bytes(c: &mut Criterion) {
+    let blob = Blob(vec![0xa5; 10 * 1024 * 1024]);
+    let mut group = c.benchmark_group("write_bytes");
+
+    // The cost of copying the bytes once, for comparison.
+    group.bench_function("copy", |b| b.iter(|| blob.0.to_vec()));
+
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(&blob).unwrap()));
+
+    group.finish();
+}
+
+#[derive(Serialize)]
+struct Record {
+    name: &'static str,
+    size: i64,
+    mode: i32,
+    uid: i32,
+    gid: i32,
+    mtime: i64,
+    ctime: i64,
+    ino: i64,
+    exists: bool,
+    new: bool,
+}
+
+fn bench_write_structs(c: &mut Criterion) {
+    let records: Vec<Record> = (0..1_000_000)
+        .map(|i| Record {
+            name: "src/some/directory/file.rs",
+            size: i * 1024,
+            mode: 0o644,
+            uid: 1000,
+            gid: 1000,
+            mtime: 1_500_000_000 + i,
+            ctime: 1_500_000_000 + i,
+            ino: i,
+            exists: i % 7 != 0,
+            new: i % 3 == 0,
+        })
+        .collect();
+    let mut group = c.benchmark_group("write_structs");
+    group.sample_size(10);
+
+    // Most of each record is its field names.
+    group.bench_function("to_writer", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            out.clear();
+            to_writer(&mut out, &records).unwrap();
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_write_large(c: &mut Criterion) {
+    // About 8 MB once encoded, which `to_vec` reaches by doubling its buffer.
+    let files: Vec<String> = (0..250_000)
+        .map(|i| format!("src/some/directory/file{}.rs", i))
+        .collect();
+    let mut group = c.benchmark_group("write_large");
+    group.sample_size(20);
+
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(&files).unwrap()));
+
+    group.bench_function("to_vec_exact", |b| b.iter(|| to_vec_exact(&files).unwrap()));
+
+    group.finish();
+}
+
+#[derive(Serialize)]
+struct Query<'a> {
+    expression: (&'a str, &'a str),
+    fields: &'a [&'a str],
+    since: &'a str,
+}
+
+fn bench_write_small(c: &mut Criterion) {
+    // A typical request, a few dozen bytes long.
+    let request = (
+        "query",
+        "/home/user/project",
+        Query {
+            expression: ("suffix", "rs"),
+            fields: &["name", "exists", "size"],
+            since: "c:1500000000:42",
+        },
+    );
+    let mut group = c.benchmark_group("write_small");
+
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(&request).unwrap()));
+
+    group.bench_function("to_writer_reused", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            out.clear();
+            to_writer(&mut out, &request).unwrap();
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_write,
+    bench_write_bytes,
+    bench_write_structs,
+    bench_write_large,
+    bench_write_small
+);
+criterion_main!(benches);
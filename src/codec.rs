@@ -0,0 +1,104 @@
+//! A Tokio codec for reading and writing Watchman PDUs.
+//!
+//! This module is only available with the `tokio` feature.
+
+use crate::de;
+use crate::error::Error;
+use crate::pdu;
+use crate::ser;
+
+use bytes::BytesMut;
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Length of the PDU magic and the tag of the integer which follows it.
+const HEADER_PREFIX_LEN: usize = 3;
+
+/// A `Decoder` and `Encoder` for values of type `T`, framed as Watchman PDUs
+/// in the native byte order.
+///
+/// Decoding waits until a whole PDU has been buffered before deserializing
+/// its body, so a header or body split across several reads is handled.
+pub struct BserCodec<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> BserCodec<T> {
+    /// Create a new codec.
+    #[inline]
+    pub fn new() -> Self {
+        BserCodec {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BserCodec<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for BserCodec<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Decoder for BserCodec<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, Error> {
+        // Reject a bad magic as soon as it arrives, rather than waiting for a
+        // length which will never be meaningful.
+        let magic_len = src.len().min(pdu::MAGIC_V1.len());
+        if src[..magic_len] != pdu::MAGIC_V1[..magic_len] {
+            return Err(Error::InvalidMagic);
+        }
+        if src.len() < HEADER_PREFIX_LEN {
+            return Ok(None);
+        }
+
+        // The width of the length is known from its tag. Anything other than
+        // an integer tag is left for the deserializer to report.
+        let width = match src[2] {
+            0x03 => 1,
+            0x04 => 2,
+            0x05 => 4,
+            0x06 => 8,
+            _ => 0,
+        };
+        let header_len = HEADER_PREFIX_LEN + width;
+        if src.len() < header_len {
+            return Ok(None);
+        }
+
+        let len = de::Deserializer::from_slice(&src[..header_len]).read_pdu_header()?;
+        let total = header_len.checked_add(len).ok_or(Error::IntegerOverflow)?;
+        if src.len() < total {
+            return Ok(None);
+        }
+
+        let frame = src.split_to(total);
+        de::from_slice_pdu(&frame).map(Some)
+    }
+}
+
+impl<T> Encoder<T> for BserCodec<T>
+where
+    T: Serialize,
+{
+    type Error = Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Error> {
+        dst.extend_from_slice(&ser::to_vec_pdu(&item)?);
+        Ok(())
+    }
+}
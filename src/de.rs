@@ -1,25 +1,188 @@
 use crate::error::{Error, Result};
+use crate::pdu::{self, Capabilities, PduHeader};
+use crate::value::RAW_VALUE_TOKEN;
 use crate::Tag;
 
-use byteorder::{ByteOrder, NativeEndian, ReadBytesExt};
+use crate::io::{self, ReadBytesExt};
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian};
+use core::cmp;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops;
+use core::ptr;
+use core::str;
 use serde::de::{self, Expected, Unexpected};
 use serde::forward_to_deserialize_any;
-use std::borrow::Cow;
-use std::cmp;
-use std::io;
-use std::marker::PhantomData;
-use std::ops;
-use std::str;
+
+/// Default limit on the declared length of a string, array or object.
+const DEFAULT_MAX_ALLOC: usize = 1 << 30;
+
+/// Default limit on how deeply arrays and objects may be nested.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Upper bound on the capacity reserved up front for a declared length, so
+/// that large inputs grow their buffers as data actually arrives.
+const MAX_PREALLOC: usize = 4096;
+
+/// Limits on the input accepted by a `Deserializer`, for reading untrusted
+/// data.
+///
+/// By default strings, arrays and objects may declare up to 2^30 elements,
+/// they may be nested 128 deep, and the input may be of any size. Strings
+/// have no limit of their own beyond that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadLimit {
+    max_depth: usize,
+    max_alloc: usize,
+    max_string_len: usize,
+    max_total_bytes: usize,
+}
+
+impl ReadLimit {
+    /// Create the default set of limits.
+    #[inline]
+    pub fn new() -> Self {
+        ReadLimit {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_alloc: DEFAULT_MAX_ALLOC,
+            max_string_len: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+
+    /// Set how deeply arrays and objects may be nested. Deeper input fails
+    /// with `Error::RecursionLimitExceeded`.
+    #[inline]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the largest declared length accepted for a string, array or
+    /// object. Longer lengths fail with `Error::LengthLimitExceeded`.
+    #[inline]
+    pub fn with_max_alloc(mut self, max_alloc: usize) -> Self {
+        self.max_alloc = max_alloc;
+        self
+    }
+
+    /// Set the longest string accepted, in bytes, separately from the limit
+    /// on arrays and objects. Longer strings fail with
+    /// `Error::StringTooLong`.
+    #[inline]
+    pub fn with_max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = max_string_len;
+        self
+    }
+
+    /// Set how many bytes may be read from the input in total, counting from
+    /// where the deserializer started. Reading past this fails with
+    /// `Error::TotalBytesLimitExceeded` before the bytes are consumed.
+    #[inline]
+    pub fn with_max_total_bytes(mut self, max_total_bytes: usize) -> Self {
+        self.max_total_bytes = max_total_bytes;
+        self
+    }
+
+    #[inline]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    #[inline]
+    pub fn max_alloc(&self) -> usize {
+        self.max_alloc
+    }
+
+    #[inline]
+    pub fn max_string_len(&self) -> usize {
+        self.max_string_len
+    }
+
+    #[inline]
+    pub fn max_total_bytes(&self) -> usize {
+        self.max_total_bytes
+    }
+}
+
+impl Default for ReadLimit {
+    fn default() -> Self {
+        ReadLimit::new()
+    }
+}
+
+/// The kind of value which comes next in the input, as returned by
+/// `Deserializer::peek_kind`.
+///
+/// This does not distinguish the widths of integers, as serializers are free
+/// to choose any width which can hold the value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValueKind {
+    Array,
+    Object,
+    String,
+    Integer,
+    Real,
+    Bool,
+    Null,
+    /// An array of objects, encoded as a template of keys followed by the
+    /// values for each object.
+    Templated,
+    /// A missing value within a row of a templated array.
+    Missing,
+}
+
+/// How strings are handed to visitors which ask for any value.
+///
+/// BSER strings are arbitrary bytes, so by default self-describing formats
+/// see them as byte buffers. Text formats such as JSON usually want them as
+/// strings instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StringMode {
+    /// Visit strings as bytes.
+    #[default]
+    Bytes,
+    /// Visit strings as `str`, failing with `Error::Utf8Error` if they are
+    /// not valid UTF-8.
+    Utf8,
+    /// Visit strings as `str`, replacing invalid UTF-8 with U+FFFD.
+    Utf8Lossy,
+    /// Visit strings as `str` when they are valid UTF-8, and as bytes
+    /// otherwise.
+    Utf8OrBytes,
+}
 
 /// A structure that deserializes BSER into Rust values.
 pub struct Deserializer<R, B = NativeEndian> {
     read: R,
     tag: Option<Tag>,
     scratch: Vec<u8>,
+    /// The offset at which the current PDU body starts, and its declared
+    /// length.
+    pdu_body: Option<(usize, usize)>,
+    limit: ReadLimit,
+    depth: usize,
+    string_mode: StringMode,
+    reject_duplicate_keys: bool,
+    bytes_from_arrays: bool,
+    null_as_empty: bool,
+    unsigned_integers: bool,
+    skip_unused_columns: bool,
+    detailed_errors: bool,
+    bool_as_int: bool,
     _marker: PhantomData<B>,
 }
 
-impl<'de, R> Deserializer<IoRead<R>, NativeEndian>
+#[cfg(feature = "std")]
+impl<R> Deserializer<IoRead<R>, NativeEndian>
 where
     R: io::Read,
 {
@@ -30,6 +193,19 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<R> Deserializer<BufReadRead<R>, NativeEndian>
+where
+    R: std::io::BufRead,
+{
+    /// Construct a deserializer for the given `io::BufRead`, which reads
+    /// strings from its buffer where possible.
+    #[inline]
+    pub fn from_bufread(read: R) -> Self {
+        Self::new(BufReadRead::new(read))
+    }
+}
+
 impl<'de> Deserializer<SliceRead<'de>, NativeEndian> {
     /// Construct a deserializer for the given byte slice.
     #[inline]
@@ -38,6 +214,40 @@ impl<'de> Deserializer<SliceRead<'de>, NativeEndian> {
     }
 }
 
+#[cfg(feature = "bytes")]
+impl<'de> Deserializer<BytesRead<'de>, NativeEndian> {
+    /// Construct a deserializer for the given `Bytes` buffer.
+    #[inline]
+    pub fn from_bytes(bytes: &'de bytes::Bytes) -> Self {
+        Self::new(BytesRead::new(bytes))
+    }
+}
+
+/// Cloning a deserializer over a slice takes a checkpoint of its position,
+/// so that one copy can try to parse a value while the other is kept to
+/// retry from the same place.
+impl<'de, B> Clone for Deserializer<SliceRead<'de>, B> {
+    fn clone(&self) -> Self {
+        Deserializer {
+            read: self.read.clone(),
+            tag: self.tag,
+            scratch: self.scratch.clone(),
+            pdu_body: self.pdu_body,
+            limit: self.limit,
+            depth: self.depth,
+            string_mode: self.string_mode,
+            reject_duplicate_keys: self.reject_duplicate_keys,
+            bytes_from_arrays: self.bytes_from_arrays,
+            null_as_empty: self.null_as_empty,
+            unsigned_integers: self.unsigned_integers,
+            skip_unused_columns: self.skip_unused_columns,
+            detailed_errors: self.detailed_errors,
+            bool_as_int: self.bool_as_int,
+            _marker: PhantomData,
+        }
+    }
+}
+
 impl<'de, R> Deserializer<R, NativeEndian>
 where
     R: Read<'de>,
@@ -48,6 +258,18 @@ where
     }
 }
 
+impl<'de, R> Deserializer<R, BigEndian>
+where
+    R: Read<'de>,
+{
+    /// Create a deserializer which reads integers and reals in network byte
+    /// order, rather than the native order used by Watchman.
+    #[inline]
+    pub fn big_endian(read: R) -> Self {
+        Self::new(read)
+    }
+}
+
 impl<'de, R, B> Deserializer<R, B>
 where
     R: Read<'de>,
@@ -60,27 +282,702 @@ where
             read,
             tag: None,
             scratch: Vec::new(),
+            pdu_body: None,
+            limit: ReadLimit::new(),
+            depth: 0,
+            string_mode: StringMode::Bytes,
+            reject_duplicate_keys: false,
+            bytes_from_arrays: false,
+            null_as_empty: false,
+            unsigned_integers: false,
+            skip_unused_columns: false,
+            detailed_errors: false,
+            bool_as_int: false,
             _marker: PhantomData,
         }
     }
 
+    /// Start reading from `read`, returning the reader used until now.
+    ///
+    /// Any value peeked at and the length of the current PDU are forgotten,
+    /// but settings such as limits and the string mode are kept, as is the
+    /// scratch buffer grown for earlier input. This lets one deserializer
+    /// be reused for many messages without reallocating.
+    pub fn reset(&mut self, read: R) -> R {
+        self.tag = None;
+        self.pdu_body = None;
+        self.depth = 0;
+        self.scratch.clear();
+        mem::replace(&mut self.read, read)
+    }
+
+    /// Set the largest declared length accepted for a string, array or
+    /// object. Longer lengths fail with `Error::LengthLimitExceeded` before
+    /// anything is allocated for them.
+    #[inline]
+    pub fn set_max_alloc(&mut self, max_alloc: usize) -> &mut Self {
+        self.limit.max_alloc = max_alloc;
+        self
+    }
+
+    /// Set the longest string accepted, in bytes. Longer strings fail with
+    /// `Error::StringTooLong` before anything is allocated for them.
+    #[inline]
+    pub fn set_max_string_len(&mut self, max_string_len: usize) -> &mut Self {
+        self.limit.max_string_len = max_string_len;
+        self
+    }
+
+    /// Set how deeply arrays and objects may be nested. Deeper input fails
+    /// with `Error::RecursionLimitExceeded` rather than overflowing the
+    /// stack.
+    #[inline]
+    pub fn set_max_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.limit.max_depth = max_depth;
+        self
+    }
+
+    /// Replace all of the limits on the input at once.
+    #[inline]
+    pub fn set_limit(&mut self, limit: ReadLimit) -> &mut Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set how strings, including object keys, are visited by
+    /// `deserialize_any`. Switch away from `StringMode::Bytes` when
+    /// transcoding into a format whose strings must be text, such as
+    /// `serde_json`.
+    #[inline]
+    pub fn set_string_mode(&mut self, string_mode: StringMode) -> &mut Self {
+        self.string_mode = string_mode;
+        self
+    }
+
+    /// Fail with `Error::DuplicateKey` when an object, or the template of a
+    /// templated array, holds the same key more than once. This tracks the
+    /// keys of each object as it is read, so it is disabled by default.
+    #[inline]
+    pub fn set_reject_duplicate_keys(&mut self, reject_duplicate_keys: bool) -> &mut Self {
+        self.reject_duplicate_keys = reject_duplicate_keys;
+        self
+    }
+
+    /// Accept an array of integers in `0..=255` where bytes are expected, as
+    /// well as a string. Some encoders write byte buffers this way, but it is
+    /// not how this crate or Watchman write them, so by default such arrays
+    /// are rejected.
+    #[inline]
+    pub fn set_bytes_from_arrays(&mut self, bytes_from_arrays: bool) -> &mut Self {
+        self.bytes_from_arrays = bytes_from_arrays;
+        self
+    }
+
+    /// Accept `Null` where a sequence or map is expected, reading it as an
+    /// empty one. Some encoders write empty collections this way. By default
+    /// `Null` is rejected there.
+    #[inline]
+    pub fn set_null_as_empty(&mut self, null_as_empty: bool) -> &mut Self {
+        self.null_as_empty = null_as_empty;
+        self
+    }
+
+    /// Visit non-negative integers with `visit_u64` rather than the signed
+    /// `visit_i*` methods when the type being deserialized into decides from
+    /// the input, as self-describing types such as `Value` do. BSER integers
+    /// are always signed, so by default they are visited as such.
+    #[inline]
+    pub fn set_unsigned_integers(&mut self, unsigned_integers: bool) -> &mut Self {
+        self.unsigned_integers = unsigned_integers;
+        self
+    }
+
+    /// Accept `True` and `False` where an integer is expected, reading them
+    /// as `1` and `0`. Some encoders write flags this way. By default they
+    /// are rejected there. Lengths within the input must still be integers.
+    #[inline]
+    pub fn set_bool_as_int(&mut self, bool_as_int: bool) -> &mut Self {
+        self.bool_as_int = bool_as_int;
+        self
+    }
+
+    /// Skip the columns of a templated array which the struct being read
+    /// from each row has no field for, without offering their keys to it.
+    ///
+    /// This saves matching each unused key against the struct's fields for
+    /// every row of a wide template. A struct with
+    /// `#[serde(deny_unknown_fields)]` no longer sees those keys, so it
+    /// doesn't reject them. Only the first 64 columns are skipped this way.
+    #[inline]
+    pub fn set_skip_unused_columns(&mut self, skip_unused_columns: bool) -> &mut Self {
+        self.skip_unused_columns = skip_unused_columns;
+        self
+    }
+
+    /// Record the path to the value being deserialized in `Message` errors,
+    /// such as those raised by a visitor given an invalid value, as an
+    /// `Error::Path`. The path is a JSON pointer such as `/files/3/name`.
+    ///
+    /// Keys are copied as objects are read so that they can be reported,
+    /// which is why this is off by default.
+    #[inline]
+    pub fn set_detailed_errors(&mut self, detailed_errors: bool) -> &mut Self {
+        self.detailed_errors = detailed_errors;
+        self
+    }
+
+    /// Read the header of a Watchman PDU, returning the length of the body
+    /// which follows it.
+    ///
+    /// The header consists of the magic bytes `0x00 0x01`, followed by a BSER
+    /// integer holding the length of the body. After calling this method,
+    /// `Deserializer::end` and `Deserializer::end_pdu` will also check that
+    /// exactly that many bytes were consumed while deserializing the body.
+    pub fn read_pdu_header(&mut self) -> Result<usize> {
+        self.read_pdu_magic(pdu::MAGIC_V1)?;
+        let len = self.read_pdu_len()?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(len, "read bser pdu header");
+        Ok(len)
+    }
+
+    /// Read the header of a BSER v2 PDU, returning the capabilities it
+    /// carries and the length of the body which follows it.
+    ///
+    /// The v2 header consists of the magic bytes `0x00 0x02`, a little-endian
+    /// `u32` holding the capabilities, and a BSER integer holding the length
+    /// of the body.
+    pub fn read_pdu_header_v2(&mut self) -> Result<(Capabilities, usize)> {
+        self.read_pdu_magic(pdu::MAGIC_V2)?;
+        let caps = Capabilities::from_bits(self.read.read_u32::<LittleEndian>()?);
+        let len = self.read_pdu_len()?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(len, capabilities = caps.bits(), "read bser v2 pdu header");
+        Ok((caps, len))
+    }
+
+    /// Read the header of a PDU which may be either v1 or v2, returning the
+    /// version it was, along with the capabilities for v2 and the length of
+    /// the body which follows it.
+    ///
+    /// This lets a client find out which version a server speaks, and adapt
+    /// the requests it sends next.
+    pub fn read_any_pdu_header(&mut self) -> Result<PduHeader> {
+        let magic = [self.read.read_u8()?, self.read.read_u8()?];
+        let capabilities = match magic {
+            pdu::MAGIC_V1 => None,
+            pdu::MAGIC_V2 => Some(Capabilities::from_bits(
+                self.read.read_u32::<LittleEndian>()?,
+            )),
+            _ => return Err(Error::InvalidMagic),
+        };
+        let body_len = self.read_pdu_len()?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            len = body_len,
+            version = magic[1],
+            capabilities = capabilities.map(Capabilities::bits),
+            "read bser pdu header"
+        );
+        Ok(PduHeader {
+            version: magic[1],
+            capabilities,
+            body_len,
+        })
+    }
+
+    fn read_pdu_magic(&mut self, expected: [u8; 2]) -> Result<()> {
+        let first = self.read.read_u8()?;
+        self.finish_pdu_magic(first, expected)
+    }
+
+    /// Check the rest of the PDU magic, once its first byte has been read.
+    fn finish_pdu_magic(&mut self, first: u8, expected: [u8; 2]) -> Result<()> {
+        let second = self.read.read_u8()?;
+        if [first, second] != expected {
+            return Err(Error::InvalidMagic);
+        }
+        Ok(())
+    }
+
+    fn read_pdu_len(&mut self) -> Result<usize> {
+        let len = self.read_usize()?;
+        self.pdu_body = Some((self.byte_offset(), len));
+        Ok(len)
+    }
+
     /// The `Deserializer::end` method should be called after a value has been
     /// fully deserialized. This allows the `Deserializer` to validate that the
     /// input stream is at the end or that it only has trailing whitespace.
     #[inline]
     pub fn end(&mut self) -> Result<()> {
+        self.check_pdu_body()?;
         match (self.tag, self.read.next()?) {
             (None, None) => Ok(()),
             _ => Err(Error::TrailingBytes),
         }
     }
 
+    /// Finish reading a PDU, for input which may hold further PDUs after it.
+    ///
+    /// Like `Deserializer::end`, this checks that the body was exactly the
+    /// length given in the PDU header, but it doesn't require the input to
+    /// end there. It returns the number of bytes consumed from the input so
+    /// far, so that callers managing their own buffers know how far to
+    /// advance them.
+    #[inline]
+    pub fn end_pdu(&mut self) -> Result<usize> {
+        self.check_pdu_body()?;
+        Ok(self.byte_offset())
+    }
+
+    /// The number of bytes consumed from the input so far, for reporting
+    /// progress through a large input. A value which has been peeked at, such
+    /// as by `peek_kind`, is not counted as consumed until it is read.
+    #[inline]
+    pub fn byte_offset(&self) -> usize {
+        self.read.byte_offset() - self.tag.is_some() as usize
+    }
+
+    /// Find out what kind of value comes next in the input, without consuming
+    /// it. The next call to deserialize or skip a value still sees it.
+    pub fn peek_kind(&mut self) -> Result<ValueKind> {
+        Ok(match self.peek_tag()? {
+            Tag::Array => ValueKind::Array,
+            Tag::Object => ValueKind::Object,
+            Tag::String => ValueKind::String,
+            Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 => ValueKind::Integer,
+            Tag::Real => ValueKind::Real,
+            Tag::True | Tag::False => ValueKind::Bool,
+            Tag::Null => ValueKind::Null,
+            Tag::Templated => ValueKind::Templated,
+            Tag::Missing => ValueKind::Missing,
+        })
+    }
+
+    /// Skip over the next value in the input without deserializing it.
+    ///
+    /// This walks the encoding directly rather than going through a visitor,
+    /// so it is cheaper than deserializing into `IgnoredAny`. The length and
+    /// depth limits still apply to the skipped value.
+    pub fn skip_value(&mut self) -> Result<()> {
+        match self.read_tag()? {
+            Tag::Array => {
+                let len = self.read_count()?;
+                self.nested(|de| (0..len).try_for_each(|_| de.skip_value()))
+            }
+            Tag::Object => {
+                let len = self.read_count()?;
+                self.nested(|de| {
+                    (0..len).try_for_each(|_| {
+                        de.expect_key()?;
+                        de.read_bytes()?;
+                        de.skip_value()
+                    })
+                })
+            }
+            Tag::String => self.read_bytes().map(drop),
+            Tag::Int8 => self.read_i8().map(drop),
+            Tag::Int16 => self.read_i16().map(drop),
+            Tag::Int32 => self.read_i32().map(drop),
+            Tag::Int64 => self.read_i64().map(drop),
+            Tag::Real => self.read_f64().map(drop),
+            Tag::True | Tag::False | Tag::Null => Ok(()),
+            Tag::Templated => {
+                self.expect_tag(Tag::Array, &"template key array")?;
+                let num_keys = self.read_count()?;
+                for _ in 0..num_keys {
+                    self.expect_key()?;
+                    self.read_bytes()?;
+                }
+
+                let len = if num_keys == 0 {
+                    self.read_len()?
+                } else {
+                    self.read_count()?
+                };
+                let slots = len.checked_mul(num_keys).ok_or(Error::IntegerOverflow)?;
+                self.nested(|de| {
+                    (0..slots).try_for_each(|_| {
+                        if de.peek_tag()? == Tag::Missing {
+                            de.tag = None;
+                            Ok(())
+                        } else {
+                            de.skip_value()
+                        }
+                    })
+                })
+            }
+            Tag::Missing => self.bad_tag(Tag::Missing, &"any value"),
+        }
+    }
+
+    /// Deserialize the value of one key of the next object in the input, or
+    /// return `None` if the object has no such key.
+    ///
+    /// This consumes the whole object, whether or not the key is found, so
+    /// the input is left just after it. The other values are skipped as
+    /// `skip_value` does, without being deserialized. If the key appears more
+    /// than once, the first of its values is used.
+    pub fn deserialize_field<T>(&mut self, key: &str) -> Result<Option<T>>
+    where
+        T: de::Deserialize<'de>,
+    {
+        self.expect_tag(Tag::Object, &"object")?;
+        let len = self.read_count()?;
+        self.nested(|de| {
+            let mut field = None;
+            for _ in 0..len {
+                de.expect_key()?;
+                let wanted = *de.read_bytes()? == *key.as_bytes() && field.is_none();
+                if wanted {
+                    field = Some(T::deserialize(&mut *de)?);
+                } else {
+                    de.skip_value()?;
+                }
+            }
+            Ok(field)
+        })
+    }
+
+    /// Copy the encoding of the next value into `out` verbatim, for
+    /// deserializing a `RawValue`. This checks the value as `skip_value`
+    /// does.
+    fn capture_value(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let tag = self.read_tag()?;
+        if tag == Tag::Missing {
+            return self.bad_tag(tag, &"any value");
+        }
+        out.push(tag as u8);
+        match tag {
+            Tag::Array => {
+                let len = self.capture_count(out)?;
+                self.nested(|de| (0..len).try_for_each(|_| de.capture_value(out)))
+            }
+            Tag::Object => {
+                let len = self.capture_count(out)?;
+                self.nested(|de| {
+                    (0..len).try_for_each(|_| {
+                        de.expect_key()?;
+                        out.push(Tag::String as u8);
+                        de.capture_string(out)?;
+                        de.capture_value(out)
+                    })
+                })
+            }
+            Tag::String => self.capture_string(out),
+            Tag::Int8 => self.capture_number("a 1-byte integer", 1, out),
+            Tag::Int16 => self.capture_number("a 2-byte integer", 2, out),
+            Tag::Int32 => self.capture_number("a 4-byte integer", 4, out),
+            Tag::Int64 => self.capture_number("an 8-byte integer", 8, out),
+            Tag::Real => self.capture_number("an 8-byte real", 8, out),
+            Tag::True | Tag::False | Tag::Null => Ok(()),
+            Tag::Templated => {
+                self.expect_tag(Tag::Array, &"template key array")?;
+                out.push(Tag::Array as u8);
+                let num_keys = self.capture_count(out)?;
+                for _ in 0..num_keys {
+                    self.expect_key()?;
+                    out.push(Tag::String as u8);
+                    self.capture_string(out)?;
+                }
+
+                let len = if num_keys == 0 {
+                    self.capture_len(out)?
+                } else {
+                    self.capture_count(out)?
+                };
+                let slots = len.checked_mul(num_keys).ok_or(Error::IntegerOverflow)?;
+                self.nested(|de| {
+                    (0..slots).try_for_each(|_| {
+                        if de.peek_tag()? == Tag::Missing {
+                            de.tag = None;
+                            out.push(Tag::Missing as u8);
+                            Ok(())
+                        } else {
+                            de.capture_value(out)
+                        }
+                    })
+                })
+            }
+            Tag::Missing => unreachable!(),
+        }
+    }
+
+    /// Deserialize the encoding of the next value for a `RawValue`. This is
+    /// borrowed from the input when it is a slice, and copied otherwise.
+    fn deserialize_raw<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let start = self.byte_offset();
+        if self.read.slice_from(start).is_some() {
+            self.skip_value()?;
+            if let Some(raw) = self.read.slice_from(start) {
+                return visitor.visit_borrowed_bytes(raw);
+            }
+        }
+
+        let mut raw = Vec::new();
+        self.capture_value(&mut raw)?;
+        visitor.visit_byte_buf(raw)
+    }
+
+    /// Copy the next `len` bytes of the input into `out`.
+    fn capture_bytes(&mut self, len: usize, out: &mut Vec<u8>) -> Result<()> {
+        let bytes = self.read.read_ref(len, &mut self.scratch)?;
+        out.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Copy the `len` byte body of a fixed-width number into `out`, like
+    /// `read_number`.
+    fn capture_number(&mut self, what: &'static str, len: usize, out: &mut Vec<u8>) -> Result<()> {
+        self.capture_bytes(len, out)
+            .map_err(|err| Error::reading(what, err))
+    }
+
+    /// Copy a string's length and contents into `out`, after its tag.
+    fn capture_string(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let len = self.capture_len(out)?;
+        self.check_string_len(len)?;
+        self.check_total_bytes(len)?;
+        self.capture_bytes(len, out)
+    }
+
+    /// Copy an encoded length into `out`, returning its value. The same
+    /// limit applies as in `read_len`.
+    fn capture_len(&mut self, out: &mut Vec<u8>) -> Result<usize> {
+        let tag = self.read_tag()?;
+        out.push(tag as u8);
+        let start = out.len();
+        let value = match tag {
+            Tag::Int8 => {
+                self.capture_number("a 1-byte integer", 1, out)?;
+                out[start] as i8 as i64
+            }
+            Tag::Int16 => {
+                self.capture_number("a 2-byte integer", 2, out)?;
+                B::read_i16(&out[start..]) as i64
+            }
+            Tag::Int32 => {
+                self.capture_number("a 4-byte integer", 4, out)?;
+                B::read_i32(&out[start..]) as i64
+            }
+            Tag::Int64 => {
+                self.capture_number("an 8-byte integer", 8, out)?;
+                B::read_i64(&out[start..])
+            }
+            tag => return self.bad_tag(tag, &"a length"),
+        };
+        self.check_len(usize_from(value)?)
+    }
+
+    /// Copy an encoded element count into `out`, returning its value. The
+    /// same checks apply as in `read_count`.
+    fn capture_count(&mut self, out: &mut Vec<u8>) -> Result<usize> {
+        let len = self.capture_len(out)?;
+        self.check_count(len)
+    }
+
+    /// Copy one row of a templated array into `out`, rewritten as an object
+    /// holding the row's present fields, as the row isn't stored contiguously.
+    fn capture_row(&mut self, keys: &[Cow<'de, [u8]>], out: &mut Vec<u8>) -> Result<()> {
+        let mut body = Vec::new();
+        let mut present = 0;
+        for key in keys {
+            if self.peek_tag()? == Tag::Missing {
+                self.tag = None;
+                continue;
+            }
+            present += 1;
+            body.push(Tag::String as u8);
+            push_len::<B>(&mut body, key.len());
+            body.extend_from_slice(key);
+            self.capture_value(&mut body)?;
+        }
+        out.push(Tag::Object as u8);
+        push_len::<B>(out, present);
+        out.extend_from_slice(&body);
+        Ok(())
+    }
+
+    /// Render the next value as indented text, as `dump_to_string` does.
+    fn dump_value(&mut self, out: &mut String, indent: usize) -> Result<()> {
+        match self.read_tag()? {
+            Tag::Array => {
+                let len = self.read_count()?;
+                out.push_str("Array [");
+                self.nested(|de| {
+                    (0..len).try_for_each(|_| {
+                        push_newline(out, indent + 1);
+                        de.dump_value(out, indent + 1)?;
+                        out.push(',');
+                        Ok(())
+                    })
+                })?;
+                if len > 0 {
+                    push_newline(out, indent);
+                }
+                out.push(']');
+            }
+            Tag::Object => {
+                let len = self.read_count()?;
+                out.push_str("Object {");
+                self.nested(|de| {
+                    (0..len).try_for_each(|_| {
+                        de.expect_key()?;
+                        push_newline(out, indent + 1);
+                        push_bytes_literal(out, &de.read_bytes()?);
+                        out.push_str(": ");
+                        de.dump_value(out, indent + 1)?;
+                        out.push(',');
+                        Ok(())
+                    })
+                })?;
+                if len > 0 {
+                    push_newline(out, indent);
+                }
+                out.push('}');
+            }
+            Tag::String => {
+                out.push_str("String(");
+                push_bytes_literal(out, &self.read_bytes()?);
+                out.push(')');
+            }
+            Tag::Int8 => out.push_str(&format!("Int8({})", self.read_i8()?)),
+            Tag::Int16 => out.push_str(&format!("Int16({})", self.read_i16()?)),
+            Tag::Int32 => out.push_str(&format!("Int32({})", self.read_i32()?)),
+            Tag::Int64 => out.push_str(&format!("Int64({})", self.read_i64()?)),
+            Tag::Real => out.push_str(&format!("Real({:?})", self.read_f64()?)),
+            Tag::True => out.push_str("True"),
+            Tag::False => out.push_str("False"),
+            Tag::Null => out.push_str("Null"),
+            Tag::Missing => out.push_str("Missing"),
+            Tag::Templated => {
+                self.expect_tag(Tag::Array, &"template key array")?;
+                let num_keys = self.read_count()?;
+                let mut keys = Vec::with_capacity(cmp::min(num_keys, MAX_PREALLOC));
+                for _ in 0..num_keys {
+                    self.expect_key()?;
+                    keys.push(self.read_bytes()?.to_vec());
+                }
+
+                out.push_str("Templated[");
+                for (i, key) in keys.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    push_bytes_literal(out, key);
+                }
+                out.push(']');
+
+                // Rows without keys have nothing to show, and there may be
+                // very many of them.
+                if num_keys == 0 {
+                    let len = self.read_len()?;
+                    out.push_str(&format!(" ({} empty rows)", len));
+                    return Ok(());
+                }
+
+                let len = self.read_count()?;
+                out.push_str(" [");
+                self.nested(|de| {
+                    (0..len).try_for_each(|_| {
+                        push_newline(out, indent + 1);
+                        out.push('{');
+                        for key in &keys {
+                            push_newline(out, indent + 2);
+                            push_bytes_literal(out, key);
+                            out.push_str(": ");
+                            de.dump_value(out, indent + 2)?;
+                            out.push(',');
+                        }
+                        push_newline(out, indent + 1);
+                        out.push_str("},");
+                        Ok(())
+                    })
+                })?;
+                if len > 0 {
+                    push_newline(out, indent);
+                }
+                out.push(']');
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the body of the current PDU, if any, was consumed exactly.
+    #[inline]
+    fn check_pdu_body(&mut self) -> Result<()> {
+        if let Some((start, expected)) = self.pdu_body.take() {
+            let actual = self.byte_offset() - start;
+            if actual != expected {
+                return Err(Error::PduLengthMismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn this deserializer into an iterator over the PDUs in its input.
+    ///
+    /// See `StreamDeserializer` for details.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T>(self) -> StreamDeserializer<'de, R, T, B>
+    where
+        T: de::Deserialize<'de>,
+    {
+        StreamDeserializer {
+            de: self,
+            failed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Record the declared length of an array, object or templated array at
+    /// the top level of the input, once its header has been read.
+    #[cfg(feature = "tracing")]
+    fn trace_container(&self, tag: Tag, len: usize) {
+        if self.depth == 0 {
+            tracing::trace!(
+                ?tag,
+                len,
+                offset = self.byte_offset(),
+                "read bser container"
+            );
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    fn trace_container(&self, _tag: Tag, _len: usize) {}
+
+    /// Run `f`, attaching the current input offset to any error it returns.
+    #[inline]
+    fn with_offset<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        f(self).map_err(|err| Error::at(self.byte_offset(), err))
+    }
+
+    /// Run `f` to read the whole input, which must end where it returns, with
+    /// the input offset attached to any error. This is how the `from_*`
+    /// functions read their input.
+    #[inline]
+    fn read_whole<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.with_offset(|de| {
+            let value = f(de)?;
+            de.end()?;
+            Ok(value)
+        })
+    }
+
     #[inline]
     fn peek_tag(&mut self) -> Result<Tag> {
         if let Some(tag) = self.tag {
             return Ok(tag);
         }
 
+        self.check_total_bytes(1)?;
         let tag = match self.read.read_u8()? {
             0x00 => Tag::Array,
             0x01 => Tag::Object,
@@ -95,12 +992,37 @@ where
             0x0a => Tag::Null,
             0x0b => Tag::Templated,
             0x0c => Tag::Missing,
-            _ => return Err(Error::MalformedTag),
+            // Point at the offending byte, rather than just past it.
+            tag => {
+                return Err(Error::at(
+                    self.read.byte_offset() - 1,
+                    Error::UnknownTag(tag),
+                ))
+            }
         };
+        // Fixed-size payloads are read directly from `self.read`, so check
+        // that they fit within the limit here.
+        self.check_total_bytes(match tag {
+            Tag::Int8 => 1,
+            Tag::Int16 => 2,
+            Tag::Int32 => 4,
+            Tag::Int64 | Tag::Real => 8,
+            _ => 0,
+        })?;
         self.tag = Some(tag);
         Ok(tag)
     }
 
+    /// Check that `len` more bytes may be read without exceeding the limit on
+    /// the total size of the input.
+    #[inline]
+    fn check_total_bytes(&self, len: usize) -> Result<()> {
+        match self.read.byte_offset().checked_add(len) {
+            Some(end) if end <= self.limit.max_total_bytes => Ok(()),
+            _ => Err(Error::TotalBytesLimitExceeded),
+        }
+    }
+
     #[inline]
     fn read_tag(&mut self) -> Result<Tag> {
         let tag = self.peek_tag()?;
@@ -109,7 +1031,7 @@ where
     }
 
     #[inline]
-    fn expect_tag(&mut self, tag: Tag, exp: &Expected) -> Result<()> {
+    fn expect_tag(&mut self, tag: Tag, exp: &dyn Expected) -> Result<()> {
         let actual = self.read_tag()?;
         if actual == tag {
             Ok(())
@@ -118,38 +1040,127 @@ where
         }
     }
 
+    /// Read the tag of an object or template key, which must be a string.
+    /// Nothing which follows another tag is read, as it can't be a key.
+    #[inline]
+    fn expect_key(&mut self) -> Result<()> {
+        match self.read_tag()? {
+            Tag::String => Ok(()),
+            _ => Err(Error::NonStringKeyInObject),
+        }
+    }
+
     #[cold]
-    fn bad_tag<T>(&mut self, tag: Tag, exp: &Expected) -> Result<T> {
+    fn bad_tag<T>(&mut self, tag: Tag, exp: &dyn Expected) -> Result<T> {
         let unexp = match tag {
             Tag::Array => Unexpected::Seq,
             Tag::Object => Unexpected::Map,
-            Tag::String => Unexpected::Bytes(match self.read_bytes()? {
-                Reference::Borrowed(s) => s,
-                Reference::Copied(s) => s,
-            }),
-            Tag::Int8 => Unexpected::Signed(self.read.read_i8()? as i64),
-            Tag::Int16 => Unexpected::Signed(self.read.read_i16::<NativeEndian>()? as i64),
-            Tag::Int32 => Unexpected::Signed(self.read.read_i32::<NativeEndian>()? as i64),
-            Tag::Int64 => Unexpected::Signed(self.read.read_i64::<NativeEndian>()?),
-            Tag::Real => Unexpected::Float(self.read.read_f64::<NativeEndian>()?),
+            Tag::String => {
+                let bytes = match self.read_bytes()? {
+                    Reference::Borrowed(s) => s,
+                    Reference::Copied(s) => s,
+                };
+                match str::from_utf8(bytes) {
+                    Ok(s) => Unexpected::Str(s),
+                    Err(_) => Unexpected::Bytes(bytes),
+                }
+            }
+            Tag::Int8 => Unexpected::Signed(self.read_i8()? as i64),
+            Tag::Int16 => Unexpected::Signed(self.read_i16()? as i64),
+            Tag::Int32 => Unexpected::Signed(self.read_i32()? as i64),
+            Tag::Int64 => Unexpected::Signed(self.read_i64()?),
+            Tag::Real => Unexpected::Float(self.read_f64()?),
             Tag::True => Unexpected::Bool(true),
             Tag::False => Unexpected::Bool(false),
             Tag::Null => Unexpected::Unit,
             Tag::Templated => Unexpected::Seq,
-            Tag::Missing => Unexpected::Other("missing field"),
+            // This is never a valid value, whatever was expected.
+            Tag::Missing => return Err(Error::UnexpectedMissing),
         };
 
         Err(de::Error::invalid_type(unexp, exp))
     }
 
+    /// Run `f` one level deeper into the input, failing if the depth limit
+    /// has been reached.
+    #[inline]
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.limit.max_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Read a length or count. Only integer tags are accepted, unlike when
+    /// deserializing a `usize`, however leniently numbers are configured to
+    /// be read.
     #[inline]
     fn read_usize(&mut self) -> Result<usize> {
-        de::Deserialize::deserialize(self)
+        let v = self.read_integer()?;
+        usize_from(v)
     }
 
+    /// Read the length of a string, array or object, checking it against the
+    /// configured limit.
     #[inline]
-    fn read_bytes<'a>(&'a mut self) -> Result<Reference<'de, 'a, [u8]>> {
+    fn read_len(&mut self) -> Result<usize> {
         let len = self.read_usize()?;
+        self.check_len(len)
+    }
+
+    #[inline]
+    fn check_len(&self, len: usize) -> Result<usize> {
+        if len > self.limit.max_alloc {
+            return Err(Error::LengthLimitExceeded);
+        }
+        Ok(len)
+    }
+
+    /// Read the length of a string, which is also held to
+    /// `ReadLimit::max_string_len`.
+    #[inline]
+    fn read_string_len(&mut self) -> Result<usize> {
+        let len = self.read_len()?;
+        self.check_string_len(len)
+    }
+
+    #[inline]
+    fn check_string_len(&self, len: usize) -> Result<usize> {
+        if len > self.limit.max_string_len {
+            return Err(Error::StringTooLong {
+                len,
+                limit: self.limit.max_string_len,
+            });
+        }
+        Ok(len)
+    }
+
+    /// Read the number of elements in an array or object. Each element takes
+    /// at least one byte, so when the amount of input left is known a count
+    /// larger than it is rejected up front.
+    #[inline]
+    fn read_count(&mut self) -> Result<usize> {
+        let len = self.read_len()?;
+        self.check_count(len)
+    }
+
+    #[inline]
+    fn check_count(&self, len: usize) -> Result<usize> {
+        if let Some(remaining) = self.read.remaining() {
+            if len > remaining {
+                return Err(Error::LengthExceedsInput);
+            }
+        }
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_bytes<'a>(&'a mut self) -> Result<Reference<'de, 'a, [u8]>> {
+        let len = self.read_string_len()?;
+        self.check_total_bytes(len)?;
         self.read.read_ref(len, &mut self.scratch)
     }
 
@@ -165,29 +1176,125 @@ where
     }
 
     #[inline]
-    fn scan_array<V>(&mut self, visitor: V) -> Result<V::Value>
+    fn scan_any_string<V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let len = self.read_usize()?;
-        visitor.visit_seq(SeqAccess {
-            de: self,
-            remaining: len,
-        })
+        let string_mode = self.string_mode;
+        visit_any_string(self.read_bytes()?, string_mode, visitor)
     }
 
     #[inline]
-    fn scan_templated<V>(&mut self, visitor: V) -> Result<V::Value>
+    fn scan_str<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.read_bytes()? {
+            Reference::Borrowed(s) => visitor.visit_borrowed_str(str::from_utf8(s)?),
+            Reference::Copied(s) => visitor.visit_str(str::from_utf8(s)?),
+        }
+    }
+
+    /// Like `scan_bytes`, but hands the visitor a buffer of its own when the
+    /// reader would copy the bytes anyway, rather than copying them through
+    /// `scratch`.
+    #[inline]
+    fn scan_byte_buf<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let len = self.read_string_len()?;
+        self.check_total_bytes(len)?;
+        match self.read.read_owned(len)? {
+            Some(buf) => visitor.visit_byte_buf(buf),
+            None => match self.read.read_ref(len, &mut self.scratch)? {
+                Reference::Borrowed(s) => visitor.visit_borrowed_bytes(s),
+                Reference::Copied(s) => visitor.visit_bytes(s),
+            },
+        }
+    }
+
+    /// Like `scan_str`, but for visitors which want an owned `String`.
+    #[inline]
+    fn scan_string<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let len = self.read_string_len()?;
+        self.check_total_bytes(len)?;
+        match self.read.read_owned(len)? {
+            Some(buf) => {
+                let s = String::from_utf8(buf).map_err(|err| err.utf8_error())?;
+                visitor.visit_string(s)
+            }
+            None => match self.read.read_ref(len, &mut self.scratch)? {
+                Reference::Borrowed(s) => visitor.visit_borrowed_str(str::from_utf8(s)?),
+                Reference::Copied(s) => visitor.visit_str(str::from_utf8(s)?),
+            },
+        }
+    }
+
+    #[inline]
+    fn scan_array<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let len = self.read_count()?;
+        self.visit_array(len, visitor)
+    }
+
+    /// Read a string for `SharedBytes`, handing it a counted reference to the
+    /// input rather than a copy when the reader holds a `Bytes` buffer.
+    #[cfg(feature = "bytes")]
+    fn deserialize_shared_bytes<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.expect_tag(Tag::String, &"string")?;
+        let len = self.read_string_len()?;
+        self.check_total_bytes(len)?;
+        let bytes = match self.read.read_ref(len, &mut self.scratch)? {
+            Reference::Borrowed(s) => s,
+            Reference::Copied(s) => return visitor.visit_bytes(s),
+        };
+        match self.read.shared_bytes(bytes) {
+            Some(shared) => {
+                crate::value::lend_shared_bytes(shared, || visitor.visit_borrowed_bytes(bytes))
+            }
+            None => visitor.visit_borrowed_bytes(bytes),
+        }
+    }
+
+    /// Visit the elements of an array, once its count has been read.
+    #[inline]
+    fn visit_array<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.trace_container(Tag::Array, len);
+        self.nested(|de| {
+            visitor.visit_seq(SeqAccess {
+                de,
+                len,
+                remaining: len,
+            })
+        })
+    }
+
+    /// Read a templated array, after its tag. If `tuple_len` is given, the
+    /// number of rows must match it.
+    #[inline]
+    fn scan_templated<V>(&mut self, tuple_len: Option<usize>, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         // Read the array containing our keys.
         self.expect_tag(Tag::Array, &"template key array")?;
 
-        let num_keys = self.read_usize()?;
-        let mut keys = Vec::<Cow<'de, [u8]>>::with_capacity(num_keys);
+        let num_keys = self.read_count()?;
+        let mut keys = Vec::<Cow<'de, [u8]>>::with_capacity(cmp::min(num_keys, MAX_PREALLOC));
         for _ in 0..num_keys {
-            self.expect_tag(Tag::String, &"template object key")?;
+            self.expect_key()?;
 
             let key = match self.read_bytes()? {
                 // XXX: We might be able to steal the scratch buffer?
@@ -196,13 +1303,35 @@ where
             };
             keys.push(key);
         }
+        if self.reject_duplicate_keys {
+            let mut seen = BTreeSet::new();
+            for key in &keys {
+                check_duplicate_key(&mut seen, key)?;
+            }
+        }
 
-        // After names comes number of items.
-        let len = self.read_usize()?;
-        visitor.visit_seq(TemplatedAccess {
-            de: self,
-            keys: &keys,
-            remaining: len,
+        // After names comes number of items. Rows without any keys take up
+        // no input, so their count can't be checked against it.
+        let len = if num_keys == 0 {
+            self.read_len()?
+        } else {
+            self.read_count()?
+        };
+        if let Some(expected) = tuple_len.filter(|&expected| expected != len) {
+            return Err(Error::TupleLengthMismatch {
+                expected,
+                actual: len,
+            });
+        }
+        self.trace_container(Tag::Templated, len);
+        self.nested(|de| {
+            visitor.visit_seq(TemplatedAccess {
+                de,
+                keys: &keys,
+                len,
+                remaining: len,
+                columns: None,
+            })
         })
     }
 
@@ -211,10 +1340,25 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let len = self.read_usize()?;
-        visitor.visit_map(MapAccess {
-            de: self,
-            remaining: len,
+        let len = self.read_count()?;
+        self.trace_container(Tag::Object, len);
+        let seen = if self.reject_duplicate_keys {
+            Some(BTreeSet::new())
+        } else {
+            None
+        };
+        let key = if self.detailed_errors {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        self.nested(|de| {
+            visitor.visit_map(MapAccess {
+                de,
+                remaining: len,
+                seen,
+                key,
+            })
         })
     }
 
@@ -224,15 +1368,155 @@ where
         V: de::Visitor<'de>,
     {
         match self.read_tag()? {
-            Tag::Int8 => visitor.visit_i8(self.read.read_i8()?),
-            Tag::Int16 => visitor.visit_i16(self.read.read_i16::<NativeEndian>()?),
-            Tag::Int32 => visitor.visit_i32(self.read.read_i32::<NativeEndian>()?),
-            Tag::Int64 => visitor.visit_i64(self.read.read_i64::<NativeEndian>()?),
-            Tag::Real => visitor.visit_f64(self.read.read_f64::<NativeEndian>()?),
+            Tag::Int8 => visitor.visit_i8(self.read_i8()?),
+            Tag::Int16 => visitor.visit_i16(self.read_i16()?),
+            Tag::Int32 => visitor.visit_i32(self.read_i32()?),
+            Tag::Int64 => visitor.visit_i64(self.read_i64()?),
+            Tag::Real => visitor.visit_f64(self.read_f64()?),
 
             tag => self.bad_tag(tag, &"number"),
         }
     }
+
+    /// Like `deserialize_prim_number`, but for integer targets, whose values
+    /// are within `range`. A `Real` with no fractional part which fits is
+    /// visited as an integer. A negative one for an unsigned target is
+    /// rejected with `Error::NegativeUnsigned`, as a negative integer would
+    /// be, and any other `Real` with `Error::LossyNumberConversion`. Booleans
+    /// are visited as `0` or `1` if `bool_as_int` is set.
+    #[inline]
+    fn deserialize_prim_integer<V>(
+        &mut self,
+        range: ops::RangeInclusive<i128>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            Tag::Real => self.tag = None,
+            Tag::True | Tag::False if self.bool_as_int => {
+                let v = self.read_tag()? == Tag::True;
+                return visitor.visit_u8(v as u8);
+            }
+            _ => return self.deserialize_prim_number(visitor),
+        }
+
+        // 2^63 and 2^64 are exact as `f64`s, so these range checks are too.
+        // Within range, a value is whole if converting it back is lossless.
+        let v = self.read_f64()?;
+        let v = if (-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&v)
+            && v as i64 as f64 == v
+        {
+            v as i64 as i128
+        } else if (0.0..18_446_744_073_709_551_616.0).contains(&v) && v as u64 as f64 == v {
+            v as u64 as i128
+        } else {
+            return Err(Error::LossyNumberConversion);
+        };
+        if v < 0 && *range.start() == 0 {
+            Err(Error::NegativeUnsigned(v as i64))
+        } else if !range.contains(&v) {
+            Err(Error::LossyNumberConversion)
+        } else if let Ok(v) = i64::try_from(v) {
+            visitor.visit_i64(v)
+        } else {
+            visitor.visit_u64(v as u64)
+        }
+    }
+
+    /// Like `deserialize_prim_integer`, but for unsigned targets. A negative
+    /// integer is rejected with `Error::NegativeUnsigned`, which names it,
+    /// rather than with whatever message the visitor gives.
+    #[inline]
+    fn deserialize_prim_unsigned<V>(
+        &mut self,
+        range: ops::RangeInclusive<i128>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 => match self.read_integer()? {
+                v if v < 0 => Err(Error::NegativeUnsigned(v)),
+                v => visitor.visit_u64(v as u64),
+            },
+            _ => self.deserialize_prim_integer(range, visitor),
+        }
+    }
+
+    /// Read the elements of an array as bytes, after its tag.
+    fn read_byte_array(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_count()?;
+        let mut bytes = Vec::with_capacity(cmp::min(len, MAX_PREALLOC));
+        self.nested(|de| {
+            for _ in 0..len {
+                let v = de.read_integer()?;
+                if !(0..=255).contains(&v) {
+                    return Err(de::Error::invalid_value(Unexpected::Signed(v), &"a byte"));
+                }
+                bytes.push(v as u8);
+            }
+            Ok(())
+        })?;
+        Ok(bytes)
+    }
+
+    /// Read any of the integer tags, widened to an `i64`.
+    fn read_integer(&mut self) -> Result<i64> {
+        match self.read_tag()? {
+            Tag::Int8 => Ok(self.read_i8()? as i64),
+            Tag::Int16 => Ok(self.read_i16()? as i64),
+            Tag::Int32 => Ok(self.read_i32()? as i64),
+            Tag::Int64 => self.read_i64(),
+
+            tag => self.bad_tag(tag, &"integer"),
+        }
+    }
+
+    /// Read the body of a fixed-width number, described by `what`. Running
+    /// out of input partway through says so, rather than being a bare
+    /// `Error::Eof`.
+    #[inline]
+    fn read_number<T>(
+        &mut self,
+        what: &'static str,
+        read: impl FnOnce(&mut R) -> io::Result<T>,
+    ) -> Result<T> {
+        read(&mut self.read).map_err(|err| Error::reading(what, err.into()))
+    }
+
+    #[inline]
+    fn read_i8(&mut self) -> Result<i8> {
+        self.read_number("a 1-byte integer", |r| r.read_i8())
+    }
+
+    #[inline]
+    fn read_i16(&mut self) -> Result<i16> {
+        self.read_number("a 2-byte integer", |r| r.read_i16::<B>())
+    }
+
+    #[inline]
+    fn read_i32(&mut self) -> Result<i32> {
+        self.read_number("a 4-byte integer", |r| r.read_i32::<B>())
+    }
+
+    #[inline]
+    fn read_i64(&mut self) -> Result<i64> {
+        self.read_number("an 8-byte integer", |r| r.read_i64::<B>())
+    }
+
+    #[inline]
+    fn read_f64(&mut self) -> Result<f64> {
+        self.read_number("an 8-byte real", |r| r.read_f64::<B>())
+    }
+}
+
+/// Convert a length read from the input, rejecting negative values.
+#[inline]
+fn usize_from(v: i64) -> Result<usize> {
+    usize::try_from(v).map_err(|_| de::Error::invalid_value(Unexpected::Signed(v), &"usize"))
 }
 
 macro_rules! deserialize_prim_number {
@@ -247,7 +1531,31 @@ macro_rules! deserialize_prim_number {
     };
 }
 
-impl<'de, 'a, R, B> de::Deserializer<'de> for &'a mut Deserializer<R, B>
+macro_rules! deserialize_prim_integer {
+    ($name:ident, $ty:ty) => {
+        #[inline]
+        fn $name<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_prim_integer(<$ty>::MIN as i128..=<$ty>::MAX as i128, visitor)
+        }
+    };
+}
+
+macro_rules! deserialize_prim_unsigned {
+    ($name:ident, $ty:ty) => {
+        #[inline]
+        fn $name<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_prim_unsigned(<$ty>::MIN as i128..=<$ty>::MAX as i128, visitor)
+        }
+    };
+}
+
+impl<'de, R, B> de::Deserializer<'de> for &mut Deserializer<R, B>
 where
     R: Read<'de>,
     B: ByteOrder,
@@ -259,19 +1567,30 @@ where
     where
         V: de::Visitor<'de>,
     {
+        if self.unsigned_integers {
+            if let Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 = self.peek_tag()? {
+                let v = self.read_integer()?;
+                return if v >= 0 {
+                    visitor.visit_u64(v as u64)
+                } else {
+                    visitor.visit_i64(v)
+                };
+            }
+        }
+
         match self.read_tag()? {
             Tag::Array => self.scan_array(visitor),
             Tag::Object => self.scan_object(visitor),
-            Tag::String => self.scan_bytes(visitor),
-            Tag::Int8 => visitor.visit_i8(self.read.read_i8()?),
-            Tag::Int16 => visitor.visit_i16(self.read.read_i16::<NativeEndian>()?),
-            Tag::Int32 => visitor.visit_i32(self.read.read_i32::<NativeEndian>()?),
-            Tag::Int64 => visitor.visit_i64(self.read.read_i64::<NativeEndian>()?),
-            Tag::Real => visitor.visit_f64(self.read.read_f64::<NativeEndian>()?),
+            Tag::String => self.scan_any_string(visitor),
+            Tag::Int8 => visitor.visit_i8(self.read_i8()?),
+            Tag::Int16 => visitor.visit_i16(self.read_i16()?),
+            Tag::Int32 => visitor.visit_i32(self.read_i32()?),
+            Tag::Int64 => visitor.visit_i64(self.read_i64()?),
+            Tag::Real => visitor.visit_f64(self.read_f64()?),
             Tag::True => visitor.visit_bool(true),
             Tag::False => visitor.visit_bool(false),
             Tag::Null => visitor.visit_unit(),
-            Tag::Templated => self.scan_templated(visitor),
+            Tag::Templated => self.scan_templated(None, visitor),
             Tag::Missing => self.bad_tag(Tag::Missing, &"any value"),
         }
     }
@@ -289,23 +1608,64 @@ where
         }
     }
 
-    deserialize_prim_number!(deserialize_i8);
-    deserialize_prim_number!(deserialize_i16);
-    deserialize_prim_number!(deserialize_i32);
-    deserialize_prim_number!(deserialize_i64);
-    deserialize_prim_number!(deserialize_u8);
-    deserialize_prim_number!(deserialize_u16);
-    deserialize_prim_number!(deserialize_u32);
-    deserialize_prim_number!(deserialize_u64);
+    deserialize_prim_integer!(deserialize_i8, i8);
+    deserialize_prim_integer!(deserialize_i16, i16);
+    deserialize_prim_integer!(deserialize_i32, i32);
+    deserialize_prim_integer!(deserialize_i64, i64);
+    deserialize_prim_unsigned!(deserialize_u8, u8);
+    deserialize_prim_unsigned!(deserialize_u16, u16);
+    deserialize_prim_unsigned!(deserialize_u32, u32);
+    deserialize_prim_unsigned!(deserialize_u64, u64);
+
+    /// Integers are visited as `i128`. Other tags are read as for the
+    /// narrower integers.
+    #[inline]
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 => {
+                visitor.visit_i128(self.read_integer()? as i128)
+            }
+            _ => self.deserialize_prim_integer(i128::MIN..=i128::MAX, visitor),
+        }
+    }
+
+    /// Integers are visited as `u128`. Other tags are read as for the
+    /// narrower integers.
+    #[inline]
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 => match self.read_integer()? {
+                v if v < 0 => Err(Error::NegativeUnsigned(v)),
+                v => visitor.visit_u128(v as u128),
+            },
+            _ => self.deserialize_prim_integer(0..=i128::MAX, visitor),
+        }
+    }
+
     deserialize_prim_number!(deserialize_f32);
     deserialize_prim_number!(deserialize_f64);
 
+    /// Reads either a one-character string, or an integer codepoint as
+    /// written by `Serializer::with_char_as_int`.
     #[inline]
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        match self.peek_tag()? {
+            Tag::Int8 | Tag::Int16 | Tag::Int32 | Tag::Int64 => {
+                let v = self.read_integer()?;
+                let c = u32::try_from(v).ok().and_then(char::from_u32);
+                visitor.visit_char(c.ok_or(Error::InvalidChar(v))?)
+            }
+            _ => self.deserialize_str(visitor),
+        }
     }
 
     #[inline]
@@ -313,7 +1673,8 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_bytes(visitor)
+        self.expect_tag(Tag::String, &"string")?;
+        self.scan_str(visitor)
     }
 
     #[inline]
@@ -321,7 +1682,8 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_byte_buf(visitor)
+        self.expect_tag(Tag::String, &"string")?;
+        self.scan_string(visitor)
     }
 
     #[inline]
@@ -329,8 +1691,14 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.expect_tag(Tag::String, &"string")?;
-        self.scan_bytes(visitor)
+        match self.read_tag()? {
+            Tag::String => self.scan_bytes(visitor),
+            Tag::Array if self.bytes_from_arrays => {
+                let bytes = self.read_byte_array()?;
+                visitor.visit_byte_buf(bytes)
+            }
+            tag => self.bad_tag(tag, &"string"),
+        }
     }
 
     #[inline]
@@ -338,7 +1706,13 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_bytes(visitor)
+        match self.peek_tag()? {
+            Tag::String => {
+                self.read_tag()?;
+                self.scan_byte_buf(visitor)
+            }
+            _ => self.deserialize_bytes(visitor),
+        }
     }
 
     #[inline]
@@ -372,10 +1746,17 @@ where
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == RAW_VALUE_TOKEN {
+            return self.deserialize_raw(visitor);
+        }
+        #[cfg(feature = "bytes")]
+        if name == crate::value::SHARED_BYTES_TOKEN {
+            return self.deserialize_shared_bytes(visitor);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -386,31 +1767,53 @@ where
     {
         match self.read_tag()? {
             Tag::Array => self.scan_array(visitor),
-            Tag::Templated => self.scan_templated(visitor),
+            Tag::Templated => self.scan_templated(None, visitor),
+            Tag::Null if self.null_as_empty => visitor.visit_seq(SeqAccess {
+                de: self,
+                len: 0,
+                remaining: 0,
+            }),
 
             tag => self.bad_tag(tag, &"array"),
         }
     }
 
+    /// Arrays, templated or not, must hold exactly `len` elements, or this
+    /// fails with `Error::TupleLengthMismatch` before any of them are read.
     #[inline]
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        match self.peek_tag()? {
+            Tag::Array => self.tag = None,
+            Tag::Templated => {
+                self.tag = None;
+                return self.scan_templated(Some(len), visitor);
+            }
+            _ => return self.deserialize_seq(visitor),
+        }
+        let actual = self.read_count()?;
+        if actual != len {
+            return Err(Error::TupleLengthMismatch {
+                expected: len,
+                actual,
+            });
+        }
+        self.visit_array(len, visitor)
     }
 
     #[inline]
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     #[inline]
@@ -418,8 +1821,17 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.expect_tag(Tag::Object, &"object")?;
-        self.scan_object(visitor)
+        match self.read_tag()? {
+            Tag::Object => self.scan_object(visitor),
+            Tag::Null if self.null_as_empty => visitor.visit_map(MapAccess {
+                de: self,
+                remaining: 0,
+                seen: None,
+                key: None,
+            }),
+
+            tag => self.bad_tag(tag, &"object"),
+        }
     }
 
     #[inline]
@@ -434,7 +1846,7 @@ where
     {
         match self.read_tag()? {
             Tag::Array => self.scan_array(visitor),
-            Tag::Templated => self.scan_templated(visitor),
+            Tag::Templated => self.scan_templated(None, visitor),
             Tag::Object => self.scan_object(visitor),
 
             tag => self.bad_tag(tag, &"struct"),
@@ -453,13 +1865,23 @@ where
     {
         match self.peek_tag()? {
             // `{ "$key": $value }`-style variant
-            Tag::Object => visitor.visit_enum(VariantAccess { de: self }),
+            Tag::Object => {
+                self.tag = None;
+                let len = self.read_len()?;
+                if len != 1 {
+                    return Err(de::Error::invalid_length(len, &"an object with one key"));
+                }
+                self.nested(|de| visitor.visit_enum(VariantAccess { de }))
+            }
 
             // "$key" style variant. Dispatch to StringLitAccess.
             Tag::String => {
                 self.tag = None;
                 let string = self.read_bytes()?;
-                visitor.visit_enum(StringLitAccess { string })
+                visitor.visit_enum(StringLitAccess {
+                    string,
+                    string_mode: StringMode::Bytes,
+                })
             }
 
             tag => self.bad_tag(tag, &"enum variant"),
@@ -479,7 +1901,16 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    /// BSER is a binary format, so types such as `IpAddr` which have a
+    /// compact form should use it. Object keys are always strings, so they
+    /// are still deserialized as human readable.
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
     }
 }
 
@@ -490,6 +1921,7 @@ where
     B: ByteOrder,
 {
     de: &'a mut Deserializer<R, B>,
+    len: usize,
     remaining: usize,
 }
 
@@ -509,7 +1941,14 @@ where
         }
 
         self.remaining -= 1;
-        Ok(Some(seed.deserialize(&mut *self.de)?))
+        let element = seed.deserialize(&mut *self.de);
+        if self.de.detailed_errors {
+            let index = self.len - self.remaining - 1;
+            return element
+                .map(Some)
+                .map_err(|err| err.in_path(&index_segment(index)));
+        }
+        Ok(Some(element?))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -522,6 +1961,10 @@ where
 struct MapAccess<'a, R: 'a, B> {
     de: &'a mut Deserializer<R, B>,
     remaining: usize,
+    /// The keys read so far, if duplicates are being rejected.
+    seen: Option<BTreeSet<Vec<u8>>>,
+    /// The last key read, if errors are to carry paths.
+    key: Option<Vec<u8>>,
 }
 
 impl<'de, 'a, R, B> de::MapAccess<'de> for MapAccess<'a, R, B>
@@ -541,16 +1984,31 @@ where
         self.remaining -= 1;
 
         // Dispatch to a `StringLitAccess` to deserialize our object key.
-        self.de.expect_tag(Tag::String, &"object key")?;
+        self.de.expect_key()?;
+        let string_mode = self.de.string_mode;
         let string = self.de.read_bytes()?;
-        Ok(Some(seed.deserialize(StringLitAccess { string })?))
+        if let Some(seen) = &mut self.seen {
+            check_duplicate_key(seen, &string)?;
+        }
+        if let Some(key) = &mut self.key {
+            key.clear();
+            key.extend_from_slice(&string);
+        }
+        Ok(Some(seed.deserialize(StringLitAccess {
+            string,
+            string_mode,
+        })?))
     }
 
     fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value>
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let value = seed.deserialize(&mut *self.de);
+        match &self.key {
+            Some(key) => value.map_err(|err| err.in_path(&key_segment(key))),
+            None => value,
+        }
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -620,7 +2078,7 @@ where
 /// Helper type used by StringLitAccess as the VariantAccess type when
 /// deserializing a unit variant. Deserializes no data, but reports an
 /// invalid_type error when attempting to deserialize non-unit variants.
-struct DummyUnitVariantAccess;
+pub(crate) struct DummyUnitVariantAccess;
 
 impl<'de> de::VariantAccess<'de> for DummyUnitVariantAccess {
     type Error = Error;
@@ -664,6 +2122,7 @@ impl<'de> de::VariantAccess<'de> for DummyUnitVariantAccess {
 /// This type can deserialize to unit variants, strings, bytes, etc.
 struct StringLitAccess<'de, 'a> {
     string: Reference<'de, 'a, [u8]>,
+    string_mode: StringMode,
 }
 
 impl<'de, 'a> de::EnumAccess<'de> for StringLitAccess<'de, 'a> {
@@ -683,15 +2142,39 @@ impl<'de, 'a> de::Deserializer<'de> for StringLitAccess<'de, 'a> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visit_any_string(self.string, self.string_mode, visitor)
+    }
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         match self.string {
-            Reference::Borrowed(s) => visitor.visit_borrowed_bytes(s),
-            Reference::Copied(s) => visitor.visit_bytes(s),
+            Reference::Borrowed(s) => visitor.visit_borrowed_str(str::from_utf8(s)?),
+            Reference::Copied(s) => visitor.visit_str(str::from_utf8(s)?),
         }
     }
 
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    #[inline]
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
     #[inline]
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
@@ -723,9 +2206,8 @@ impl<'de, 'a> de::Deserializer<'de> for StringLitAccess<'de, 'a> {
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct seq tuple tuple_struct map struct
-        identifier ignored_any
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
     }
 }
 
@@ -736,7 +2218,11 @@ impl<'de, 'a> de::Deserializer<'de> for StringLitAccess<'de, 'a> {
 struct TemplatedAccess<'de, 'a, R, B> {
     de: &'a mut Deserializer<R, B>,
     keys: &'a [Cow<'de, [u8]>],
+    len: usize,
     remaining: usize,
+    /// The fields of the struct last read from a row, and the mask of the
+    /// columns it uses.
+    columns: Option<(&'static [&'static str], u64)>,
 }
 
 impl<'de, 'a, R, B> de::SeqAccess<'de> for TemplatedAccess<'de, 'a, R, B>
@@ -755,7 +2241,15 @@ where
         }
 
         self.remaining -= 1;
-        Ok(Some(seed.deserialize(self)?))
+        let index = self.len - self.remaining - 1;
+        let detailed_errors = self.de.detailed_errors;
+        let row = seed.deserialize(self);
+        if detailed_errors {
+            return row
+                .map(Some)
+                .map_err(|err| err.in_path(&index_segment(index)));
+        }
+        Ok(Some(row?))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -763,7 +2257,7 @@ where
     }
 }
 
-impl<'de, 'a, 'b, R, B> de::Deserializer<'de> for &'b mut TemplatedAccess<'de, 'a, R, B>
+impl<'de, 'a, R, B> de::Deserializer<'de> for &mut TemplatedAccess<'de, 'a, R, B>
 where
     R: Read<'de> + 'a,
     B: ByteOrder,
@@ -778,14 +2272,51 @@ where
         visitor.visit_map(TemplatedMapAccess {
             de: self.de,
             keys: self.keys.iter(),
+            key: None,
+        })
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if !self.de.skip_unused_columns {
+            return self.deserialize_any(visitor);
+        }
+        // Each row is usually read as the same struct, so the mask is only
+        // worked out again when the fields change.
+        let used = match self.columns {
+            Some((cached, used)) if ptr::eq(cached, fields) => used,
+            _ => {
+                let used = column_mask(self.keys, fields);
+                self.columns = Some((fields, used));
+                used
+            }
+        };
+        visitor.visit_map(ColumnMapAccess {
+            de: self.de,
+            keys: self.keys.iter(),
+            key: None,
+            used,
         })
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == RAW_VALUE_TOKEN {
+            let mut raw = Vec::new();
+            self.de.capture_row(self.keys, &mut raw)?;
+            return visitor.visit_byte_buf(raw);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -800,21 +2331,151 @@ where
 
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct seq tuple enum tuple_struct map struct
+        byte_buf unit unit_struct seq tuple enum tuple_struct map
         identifier ignored_any
     }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
 }
 
-// `MapAccess` implementation for maps within a templated sequence.
-struct TemplatedMapAccess<'de, 'a, R: 'a, B> {
-    de: &'a mut Deserializer<R, B>,
-    keys: std::slice::Iter<'a, Cow<'de, [u8]>>,
+/// A mask with a bit set for each of the first 64 keys of a template which
+/// is one of `fields`, and for every key after those.
+fn column_mask(keys: &[Cow<'_, [u8]>], fields: &[&str]) -> u64 {
+    let mut used = !0;
+    for (i, key) in keys.iter().enumerate().take(64) {
+        if !fields.iter().any(|field| field.as_bytes() == &key[..]) {
+            used &= !(1 << i);
+        }
+    }
+    used
 }
 
-impl<'de, 'a, R, B> de::MapAccess<'de> for TemplatedMapAccess<'de, 'a, R, B>
-where
-    R: Read<'de> + 'a,
-    B: ByteOrder,
+/// Format a key as a segment of an error's JSON pointer, escaping `~` and
+/// `/` within it.
+fn key_segment(key: &[u8]) -> String {
+    let mut segment = String::from("/");
+    for c in String::from_utf8_lossy(key).chars() {
+        match c {
+            '~' => segment.push_str("~0"),
+            '/' => segment.push_str("~1"),
+            c => segment.push(c),
+        }
+    }
+    segment
+}
+
+/// Format an index as a segment of an error's JSON pointer.
+fn index_segment(index: usize) -> String {
+    format!("/{}", index)
+}
+
+/// Start a new line of a dump, indented to the given depth.
+fn push_newline(out: &mut String, indent: usize) {
+    out.push('\n');
+    for _ in 0..indent {
+        out.push_str("    ");
+    }
+}
+
+/// Write a string for a dump, as a Rust string literal if it is valid UTF-8
+/// and as a byte string literal otherwise.
+fn push_bytes_literal(out: &mut String, bytes: &[u8]) {
+    match str::from_utf8(bytes) {
+        Ok(s) => out.push_str(&format!("{:?}", s)),
+        Err(_) => {
+            out.push_str("b\"");
+            for &b in bytes {
+                out.extend(core::ascii::escape_default(b).map(char::from));
+            }
+            out.push('"');
+        }
+    }
+}
+
+/// Encode a length in the smallest integer which holds it, as the serializer
+/// does.
+fn push_len<B: ByteOrder>(out: &mut Vec<u8>, len: usize) {
+    if len <= i8::MAX as usize {
+        out.push(Tag::Int8 as u8);
+        out.push(len as u8);
+    } else if len <= i16::MAX as usize {
+        let mut buf = [0; 2];
+        B::write_i16(&mut buf, len as i16);
+        out.push(Tag::Int16 as u8);
+        out.extend_from_slice(&buf);
+    } else if len <= i32::MAX as usize {
+        let mut buf = [0; 4];
+        B::write_i32(&mut buf, len as i32);
+        out.push(Tag::Int32 as u8);
+        out.extend_from_slice(&buf);
+    } else {
+        let mut buf = [0; 8];
+        B::write_i64(&mut buf, len as i64);
+        out.push(Tag::Int64 as u8);
+        out.extend_from_slice(&buf);
+    }
+}
+
+/// Record `key` as seen, failing if it already was.
+fn check_duplicate_key(seen: &mut BTreeSet<Vec<u8>>, key: &[u8]) -> Result<()> {
+    if seen.insert(key.to_owned()) {
+        Ok(())
+    } else {
+        Err(Error::DuplicateKey(
+            String::from_utf8_lossy(key).into_owned(),
+        ))
+    }
+}
+
+/// Visit a string read for `deserialize_any` as the given `StringMode` asks.
+fn visit_any_string<'de, V>(
+    string: Reference<'de, '_, [u8]>,
+    string_mode: StringMode,
+    visitor: V,
+) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    match (string_mode, string) {
+        (StringMode::Bytes, Reference::Borrowed(s)) => visitor.visit_borrowed_bytes(s),
+        (StringMode::Bytes, Reference::Copied(s)) => visitor.visit_bytes(s),
+        (StringMode::Utf8, Reference::Borrowed(s)) => {
+            visitor.visit_borrowed_str(str::from_utf8(s)?)
+        }
+        (StringMode::Utf8, Reference::Copied(s)) => visitor.visit_str(str::from_utf8(s)?),
+        (StringMode::Utf8Lossy, Reference::Borrowed(s)) => match String::from_utf8_lossy(s) {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        },
+        (StringMode::Utf8Lossy, Reference::Copied(s)) => {
+            visitor.visit_str(&String::from_utf8_lossy(s))
+        }
+        (StringMode::Utf8OrBytes, Reference::Borrowed(s)) => match str::from_utf8(s) {
+            Ok(s) => visitor.visit_borrowed_str(s),
+            Err(_) => visitor.visit_borrowed_bytes(s),
+        },
+        (StringMode::Utf8OrBytes, Reference::Copied(s)) => match str::from_utf8(s) {
+            Ok(s) => visitor.visit_str(s),
+            Err(_) => visitor.visit_bytes(s),
+        },
+    }
+}
+
+// `MapAccess` implementation for maps within a templated sequence.
+struct TemplatedMapAccess<'de, 'a, R: 'a, B> {
+    de: &'a mut Deserializer<R, B>,
+    keys: core::slice::Iter<'a, Cow<'de, [u8]>>,
+    /// The key of the value which comes next.
+    key: Option<&'a [u8]>,
+}
+
+impl<'de, 'a, R, B> de::MapAccess<'de> for TemplatedMapAccess<'de, 'a, R, B>
+where
+    R: Read<'de> + 'a,
+    B: ByteOrder,
 {
     type Error = Error;
 
@@ -823,7 +2484,7 @@ where
         T: de::DeserializeSeed<'de>,
     {
         // Loop over our keys until we find a non-missing value.
-        while let Some(key) = self.keys.next() {
+        for key in self.keys.by_ref() {
             // If we read in a Tag:Missing, skip it and move to the next key.
             if self.de.peek_tag()? == Tag::Missing {
                 self.de.tag = None;
@@ -831,11 +2492,13 @@ where
             }
 
             // We've found a non-missing key, return it.
+            self.key = Some(key);
             return Ok(Some(seed.deserialize(StringLitAccess {
                 string: match key {
                     Cow::Owned(s) => Reference::Copied(&s[..]),
                     Cow::Borrowed(s) => Reference::Borrowed(&s[..]),
                 },
+                string_mode: self.de.string_mode,
             })?));
         }
 
@@ -846,7 +2509,74 @@ where
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let value = seed.deserialize(&mut *self.de);
+        match self.key {
+            Some(key) if self.de.detailed_errors => {
+                value.map_err(|err| err.in_path(&key_segment(key)))
+            }
+            _ => value,
+        }
+    }
+}
+
+/// `MapAccess` implementation for a row of a templated sequence read as a
+/// struct, which skips the columns the struct has no field for.
+struct ColumnMapAccess<'de, 'a, R: 'a, B> {
+    de: &'a mut Deserializer<R, B>,
+    keys: core::slice::Iter<'a, Cow<'de, [u8]>>,
+    /// The key of the value which comes next.
+    key: Option<&'a [u8]>,
+    /// A bit for each of the remaining keys, from the lowest, which is clear
+    /// if its value is to be skipped.
+    used: u64,
+}
+
+impl<'de, 'a, R, B> de::MapAccess<'de> for ColumnMapAccess<'de, 'a, R, B>
+where
+    R: Read<'de> + 'a,
+    B: ByteOrder,
+{
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        for key in self.keys.by_ref() {
+            let used = self.used & 1 != 0;
+            // Keys past the first 64 are always used.
+            self.used = self.used >> 1 | 1 << 63;
+
+            match self.de.peek_tag()? {
+                Tag::Missing => self.de.tag = None,
+                _ if !used => self.de.skip_value()?,
+                _ => {
+                    self.key = Some(key);
+                    return Ok(Some(seed.deserialize(StringLitAccess {
+                        string: match key {
+                            Cow::Owned(s) => Reference::Copied(&s[..]),
+                            Cow::Borrowed(s) => Reference::Borrowed(&s[..]),
+                        },
+                        string_mode: self.de.string_mode,
+                    })?));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(&mut *self.de);
+        match self.key {
+            Some(key) if self.de.detailed_errors => {
+                value.map_err(|err| err.in_path(&key_segment(key)))
+            }
+            _ => value,
+        }
     }
 }
 
@@ -879,57 +2609,365 @@ pub trait Read<'de>: private::Sealed + io::Read {
     #[doc(hidden)]
     fn next(&mut self) -> Result<Option<u8>>;
 
+    #[doc(hidden)]
+    fn byte_offset(&self) -> usize;
+
     #[doc(hidden)]
     fn read_ref<'s>(
-        &mut self,
+        &'s mut self,
         len: usize,
         scratch: &'s mut Vec<u8>,
     ) -> Result<Reference<'de, 's, [u8]>>;
+
+    /// Read `len` bytes into a buffer of their own, for readers which can't
+    /// lend them out of the input. Returns `None` for those which can, and
+    /// `read_ref` should be used instead.
+    #[doc(hidden)]
+    fn read_owned(&mut self, _len: usize) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// The number of bytes left in the input, if it is known.
+    #[doc(hidden)]
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    /// The input from offset `start` up to the current position, for
+    /// readers which hold the whole input.
+    #[doc(hidden)]
+    fn slice_from(&self, _start: usize) -> Option<&'de [u8]> {
+        None
+    }
+
+    /// A counted reference to `bytes`, which were lent out of the input, for
+    /// readers which hold it in a `Bytes` buffer.
+    #[cfg(feature = "bytes")]
+    #[doc(hidden)]
+    fn shared_bytes(&self, _bytes: &'de [u8]) -> Option<bytes::Bytes> {
+        None
+    }
 }
 
 /// BSER input source which reads from an std::io::Read stream.
+#[cfg(feature = "std")]
 pub struct IoRead<R: io::Read> {
     read: R,
+    offset: usize,
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> IoRead<R> {
     /// Create a new `io::Read` adapter.
     pub fn new(read: R) -> Self {
-        IoRead { read }
+        IoRead { read, offset: 0 }
     }
 }
 
+#[cfg(feature = "std")]
 impl<'de, R: io::Read> Read<'de> for IoRead<R> {
     fn next(&mut self) -> Result<Option<u8>> {
         // Read a byte from the reader, and return it.
         let mut buf = [b'\0'; 1];
-        let n = io::Read::read(&mut self.read, &mut buf)?;
-        if n == 0 {
-            Ok(None)
-        } else {
-            Ok(Some(buf[0]))
+        loop {
+            match io::Read::read(&mut self.read, &mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.offset += 1;
+                    return Ok(Some(buf[0]));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
         }
     }
 
+    fn byte_offset(&self) -> usize {
+        self.offset
+    }
+
     fn read_ref<'s>(
-        &mut self,
+        &'s mut self,
         len: usize,
         scratch: &'s mut Vec<u8>,
     ) -> Result<Reference<'de, 's, [u8]>> {
-        // Grow our backing buffer to the correct size.
-        scratch.resize(len, b'\0');
-        io::Read::read_exact(&mut self.read, &mut scratch[..])?;
+        // Grow our backing buffer as the data arrives, rather than trusting
+        // the declared length up front.
+        scratch.clear();
+        let n = io::Read::read_to_end(&mut io::Read::take(&mut self.read, len as u64), scratch)?;
+        if n != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        self.offset += len;
         Ok(Reference::Copied(&scratch[..]))
     }
+
+    fn read_owned(&mut self, len: usize) -> Result<Option<Vec<u8>>> {
+        let mut buf = Vec::with_capacity(cmp::min(len, MAX_PREALLOC));
+        self.read_ref(len, &mut buf)?;
+        Ok(Some(buf))
+    }
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> io::Read for IoRead<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.read.read(buf)
+        let n = self.read.read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+/// BSER input source which reads from an std::io::Read stream through an
+/// internal buffer.
+///
+/// Unlike `IoRead`, which reads tags and small integers from the stream a
+/// byte at a time, this reads the stream in chunks. This makes it much faster
+/// for unbuffered streams like sockets, but it may read past the end of the
+/// value being deserialized, so any bytes which follow it in the stream are
+/// lost when this is dropped.
+#[cfg(feature = "std")]
+pub struct BufIoRead<R: io::Read> {
+    read: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    offset: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> BufIoRead<R> {
+    /// Create a new buffered `io::Read` adapter with a default buffer size.
+    pub fn new(read: R) -> Self {
+        Self::with_capacity(8 * 1024, read)
+    }
+
+    /// Create a new buffered `io::Read` adapter with the given buffer size.
+    pub fn with_capacity(capacity: usize, read: R) -> Self {
+        BufIoRead {
+            read,
+            buf: vec![0; cmp::max(capacity, 1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            offset: 0,
+        }
+    }
+
+    /// The bytes which have been read into the buffer but not consumed yet,
+    /// refilling it from the stream if it is empty.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.filled {
+            match self.read.read(&mut self.buf) {
+                Ok(n) => {
+                    self.filled = n;
+                    self.pos = 0;
+                    break;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+        self.offset += amt;
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::Read> Read<'de> for BufIoRead<R> {
+    fn next(&mut self) -> Result<Option<u8>> {
+        match self.fill_buf()?.first() {
+            Some(&ch) => {
+                self.consume(1);
+                Ok(Some(ch))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.offset
+    }
+
+    fn read_ref<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        // Drain what we have buffered first, and then read the remainder
+        // straight into the scratch buffer, growing it as the data arrives.
+        scratch.clear();
+        let available = &self.buf[self.pos..self.filled];
+        let amt = cmp::min(available.len(), len);
+        scratch.extend_from_slice(&available[..amt]);
+        self.consume(amt);
+
+        let rest = len - amt;
+        if rest > 0 {
+            let n =
+                io::Read::read_to_end(&mut io::Read::take(&mut self.read, rest as u64), scratch)?;
+            if n != rest {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            self.offset += rest;
+        }
+        Ok(Reference::Copied(&scratch[..]))
+    }
+
+    fn read_owned(&mut self, len: usize) -> Result<Option<Vec<u8>>> {
+        let mut buf = Vec::with_capacity(cmp::min(len, MAX_PREALLOC));
+        self.read_ref(len, &mut buf)?;
+        Ok(Some(buf))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> io::Read for BufIoRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let amt = cmp::min(buf.len(), available.len());
+        buf[..amt].copy_from_slice(&available[..amt]);
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+/// BSER input source which reads from an std::io::BufRead, such as an
+/// `io::BufReader`.
+///
+/// Strings which are already in the reader's buffer are handed out from
+/// there rather than being copied into scratch space. They are only borrowed
+/// until the next read, so they are visited as transient bytes rather than
+/// being borrowed for `'de`. Strings which span a refill of the buffer are
+/// copied as `IoRead` would.
+///
+/// Unlike `BufIoRead`, bytes which follow the value stay in the `BufRead`
+/// and can be read from it afterwards.
+#[cfg(feature = "std")]
+pub struct BufReadRead<R: std::io::BufRead> {
+    read: R,
+    /// Bytes from the front of the buffer which have been handed out by
+    /// `read_ref`, and which must be consumed before reading any further.
+    pending: usize,
+    offset: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> BufReadRead<R> {
+    /// Create a new `io::BufRead` adapter.
+    pub fn new(read: R) -> Self {
+        BufReadRead {
+            read,
+            pending: 0,
+            offset: 0,
+        }
+    }
+
+    #[inline]
+    fn consume_pending(&mut self) {
+        if self.pending > 0 {
+            self.read.consume(self.pending);
+            self.pending = 0;
+        }
+    }
+
+    /// Like `BufRead::fill_buf`, but retries reads which were interrupted.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let available = loop {
+            match self.read.fill_buf() {
+                Ok(buf) => break buf.len(),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        // The buffer can't be returned from within the loop, but asking for
+        // it again won't read any further while it isn't empty.
+        if available == 0 {
+            return Ok(&[]);
+        }
+        self.read.fill_buf()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: std::io::BufRead> Read<'de> for BufReadRead<R> {
+    fn next(&mut self) -> Result<Option<u8>> {
+        self.consume_pending();
+        match self.fill_buf()?.first() {
+            Some(&ch) => {
+                self.read.consume(1);
+                self.offset += 1;
+                Ok(Some(ch))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.offset
+    }
+
+    fn read_ref<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        self.consume_pending();
+        if self.fill_buf()?.len() >= len {
+            // The buffer can't be borrowed across the call to `consume`, so
+            // leave that until the next read.
+            self.pending = len;
+            self.offset += len;
+            return Ok(Reference::Copied(&self.fill_buf()?[..len]));
+        }
+
+        scratch.clear();
+        let n = io::Read::read_to_end(&mut io::Read::take(&mut self.read, len as u64), scratch)?;
+        if n != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        self.offset += len;
+        Ok(Reference::Copied(&scratch[..]))
+    }
+
+    fn read_owned(&mut self, len: usize) -> Result<Option<Vec<u8>>> {
+        self.consume_pending();
+        if self.fill_buf()?.len() >= len {
+            let buf = self.fill_buf()?[..len].to_vec();
+            self.read.consume(len);
+            self.offset += len;
+            return Ok(Some(buf));
+        }
+
+        let mut buf = Vec::with_capacity(cmp::min(len, MAX_PREALLOC));
+        self.read_ref(len, &mut buf)?;
+        Ok(Some(buf))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> io::Read for BufReadRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.consume_pending();
+        let n = self.read.read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> Drop for BufReadRead<R> {
+    fn drop(&mut self) {
+        // Leave the reader just past the last value read from it.
+        self.consume_pending();
     }
 }
 
 /// BSER input source which reads from a slice of bytes.
+#[derive(Clone)]
 pub struct SliceRead<'de> {
     slice: &'de [u8],
     index: usize,
@@ -953,8 +2991,12 @@ impl<'de> Read<'de> for SliceRead<'de> {
         }
     }
 
+    fn byte_offset(&self) -> usize {
+        self.index
+    }
+
     fn read_ref<'s>(
-        &mut self,
+        &'s mut self,
         len: usize,
         _scratch: &'s mut Vec<u8>,
     ) -> Result<Reference<'de, 's, [u8]>> {
@@ -967,11 +3009,20 @@ impl<'de> Read<'de> for SliceRead<'de> {
         }
         Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
     }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.slice.len().saturating_sub(self.index))
+    }
+
+    fn slice_from(&self, start: usize) -> Option<&'de [u8]> {
+        Some(&self.slice[start..self.index])
+    }
 }
 
 impl<'de> io::Read for SliceRead<'de> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let rest = &self.slice[self.index..];
+        // An index past the end is treated as the end, rather than panicking.
+        let rest = self.slice.get(self.index..).unwrap_or(&[]);
 
         let amt = cmp::min(buf.len(), rest.len());
         buf[..amt].copy_from_slice(&rest[..amt]);
@@ -981,35 +3032,500 @@ impl<'de> io::Read for SliceRead<'de> {
     }
 }
 
+/// BSER input source which reads from a `bytes::Bytes` buffer.
+///
+/// Strings are lent out of the buffer as they are by `SliceRead`, and
+/// `SharedBytes` values take a counted reference to it rather than copying,
+/// so they may outlive the borrow of the buffer.
+///
+/// This type is only available with the `bytes` feature.
+#[cfg(feature = "bytes")]
+#[derive(Clone)]
+pub struct BytesRead<'de> {
+    bytes: &'de bytes::Bytes,
+    slice: SliceRead<'de>,
+}
+
+#[cfg(feature = "bytes")]
+impl<'de> BytesRead<'de> {
+    /// Create a new `Bytes` adapter.
+    pub fn new(bytes: &'de bytes::Bytes) -> Self {
+        BytesRead {
+            bytes,
+            slice: SliceRead::new(bytes),
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<'de> Read<'de> for BytesRead<'de> {
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        self.slice.next()
+    }
+
+    #[inline]
+    fn byte_offset(&self) -> usize {
+        self.slice.byte_offset()
+    }
+
+    #[inline]
+    fn read_ref<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        self.slice.read_ref(len, scratch)
+    }
+
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        self.slice.remaining()
+    }
+
+    #[inline]
+    fn slice_from(&self, start: usize) -> Option<&'de [u8]> {
+        self.slice.slice_from(start)
+    }
+
+    #[inline]
+    fn shared_bytes(&self, bytes: &'de [u8]) -> Option<bytes::Bytes> {
+        Some(self.bytes.slice_ref(bytes))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<'de> io::Read for BytesRead<'de> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.slice.read(buf)
+    }
+}
+
 /// Prevent users from implementing the `Read` trait.
 mod private {
     pub trait Sealed {}
 }
 
+#[cfg(feature = "std")]
 impl<R> private::Sealed for IoRead<R> where R: io::Read {}
+#[cfg(feature = "std")]
+impl<R> private::Sealed for BufIoRead<R> where R: io::Read {}
+#[cfg(feature = "std")]
+impl<R> private::Sealed for BufReadRead<R> where R: std::io::BufRead {}
 impl<'a> private::Sealed for SliceRead<'a> {}
+#[cfg(feature = "bytes")]
+impl<'a> private::Sealed for BytesRead<'a> {}
+
+// ----------------------------------------------------------------------------
+
+/// Iterator over a stream of back-to-back Watchman PDUs, such as the
+/// responses to a long-lived subscription.
+///
+/// Each call to `next` reads one PDU header and the value in its body. The
+/// iterator ends cleanly if the input is exhausted between PDUs, while input
+/// which stops partway through a PDU produces an error. Once an error has been
+/// returned the position within the stream is unknown, so the iterator yields
+/// nothing further.
+pub struct StreamDeserializer<'de, R, T, B = NativeEndian> {
+    de: Deserializer<R, B>,
+    failed: bool,
+    _marker: PhantomData<(&'de (), T)>,
+}
+
+impl<'de, R, T, B> StreamDeserializer<'de, R, T, B>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+    B: ByteOrder,
+{
+    /// Create a stream deserializer reading from the given `Read`
+    /// implementation.
+    #[inline]
+    pub fn new(read: R) -> Self {
+        Deserializer::new(read).into_iter()
+    }
+
+    /// Number of bytes consumed from the input so far. Between PDUs this is
+    /// the offset of the start of the next one.
+    #[inline]
+    pub fn byte_offset(&self) -> usize {
+        self.de.byte_offset()
+    }
+
+    fn next_pdu(&mut self, first: u8) -> Result<T> {
+        self.de.with_offset(|de| {
+            de.finish_pdu_magic(first, pdu::MAGIC_V1)?;
+            de.read_pdu_len()?;
+            let value = T::deserialize(&mut *de)?;
+            de.check_pdu_body()?;
+            Ok(value)
+        })
+    }
+}
+
+impl<'de, R, T, B> Iterator for StreamDeserializer<'de, R, T, B>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+    B: ByteOrder,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+
+        let result = match self.de.read.next() {
+            Ok(None) => return None,
+            Ok(Some(first)) => self.next_pdu(first),
+            Err(err) => Err(Error::at(self.de.byte_offset(), err)),
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
 
 // ----------------------------------------------------------------------------
 
 /// Deserialize a `bser` value from an `io::Read`
+#[cfg(feature = "std")]
 pub fn from_reader<R, T>(rdr: R) -> Result<T>
 where
     R: io::Read,
     T: de::DeserializeOwned,
 {
     let mut de = Deserializer::native(IoRead::new(rdr));
-    let value = de::Deserialize::deserialize(&mut de)?;
-    de.end()?;
-    Ok(value)
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a single `bser` value from the start of an `io::Read`,
+/// returning it along with the number of bytes it took up.
+///
+/// Unlike `from_reader`, anything which follows the value is allowed. The
+/// reader is read from exactly as far as the end of the value, so passing
+/// `&mut reader` leaves it positioned to read whatever comes next. No
+/// buffering is done to make this possible, so the reader sees many small
+/// reads; wrap it in an `io::BufReader` first if that is costly, in which
+/// case the bytes which follow may be held in the `BufReader`'s buffer.
+#[cfg(feature = "std")]
+pub fn from_reader_one<R, T>(rdr: R) -> Result<(T, usize)>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.with_offset(|de| {
+        let value = de::Deserialize::deserialize(&mut *de)?;
+        Ok((value, de.byte_offset()))
+    })
 }
 
 /// Deserialize a `bser` value from a byte slice
+///
+/// Strings are lent out of `v` rather than copied, so types which borrow
+/// them, such as `&str`, `&[u8]` and `RawValue`, are read without allocating.
+/// Types which own their strings still copy them. Beyond what the value
+/// being built allocates, the deserializer only allocates a vector for the
+/// keys of each templated array, whose keys are borrowed too, and a set of
+/// keys when duplicates are rejected.
 pub fn from_slice<'de, T>(v: &'de [u8]) -> Result<T>
 where
     T: de::Deserialize<'de>,
 {
     let mut de = Deserializer::native(SliceRead::new(v));
-    let value = de::Deserialize::deserialize(&mut de)?;
-    de.end()?;
-    Ok(value)
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a single `bser` value from the start of a byte slice,
+/// returning it along with the rest of the slice.
+///
+/// Unlike `from_slice`, anything which follows the value is allowed, so
+/// values may be interleaved with other data, or read one after another
+/// without PDU framing.
+pub fn from_slice_partial<'de, T>(v: &'de [u8]) -> Result<(T, &'de [u8])>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.with_offset(|de| {
+        let value = de::Deserialize::deserialize(&mut *de)?;
+        Ok((value, &v[de.byte_offset()..]))
+    })
+}
+
+/// Deserialize a `bser` value from a `bytes::Bytes` buffer.
+///
+/// This borrows from the buffer as `from_slice` does, except that
+/// `SharedBytes` values hold a counted reference to it rather than a copy,
+/// so that a type with no borrows can still avoid copying its large strings.
+///
+/// This function is only available with the `bytes` feature.
+#[cfg(feature = "bytes")]
+pub fn from_bytes<'de, T>(bytes: &'de bytes::Bytes) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::from_bytes(bytes);
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a `bser` value from an `io::Read`, rejecting input which
+/// exceeds the given limits.
+#[cfg(feature = "std")]
+pub fn from_reader_with<R, T>(limit: ReadLimit, rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.set_limit(limit);
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a `bser` value from a byte slice, rejecting input which
+/// exceeds the given limits.
+pub fn from_slice_with<'de, T>(limit: ReadLimit, v: &'de [u8]) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.set_limit(limit);
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a `bser` value in network byte order from an `io::Read`
+#[cfg(feature = "std")]
+pub fn from_reader_be<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::big_endian(IoRead::new(rdr));
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a `bser` value in network byte order from a byte slice
+pub fn from_slice_be<'de, T>(v: &'de [u8]) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::big_endian(SliceRead::new(v));
+    de.read_whole(|de| T::deserialize(de))
+}
+
+/// Deserialize a `bser` value framed as a Watchman PDU from an `io::Read`
+#[cfg(feature = "std")]
+pub fn from_reader_pdu<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.read_whole(|de| {
+        de.read_pdu_header()?;
+        T::deserialize(de)
+    })
+}
+
+/// Deserialize a `bser` value framed as a Watchman PDU from a byte slice
+pub fn from_slice_pdu<'de, T>(v: &'de [u8]) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.read_whole(|de| {
+        de.read_pdu_header()?;
+        T::deserialize(de)
+    })
+}
+
+/// Deserialize a `bser` value framed as a Watchman v2 PDU from an `io::Read`,
+/// returning it along with the capabilities from the PDU header.
+#[cfg(feature = "std")]
+pub fn from_reader_pdu_v2<R, T>(rdr: R) -> Result<(T, Capabilities)>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.read_whole(|de| {
+        let (caps, _) = de.read_pdu_header_v2()?;
+        Ok((T::deserialize(de)?, caps))
+    })
+}
+
+/// Deserialize a `bser` value framed as a Watchman v2 PDU from a byte slice,
+/// returning it along with the capabilities from the PDU header.
+pub fn from_slice_pdu_v2<'de, T>(v: &'de [u8]) -> Result<(T, Capabilities)>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.read_whole(|de| {
+        let (caps, _) = de.read_pdu_header_v2()?;
+        Ok((T::deserialize(de)?, caps))
+    })
+}
+
+/// Deserialize a `bser` value framed as a Watchman PDU of either version from
+/// an `io::Read`, returning it along with the PDU header.
+#[cfg(feature = "std")]
+pub fn from_reader_pdu_any<R, T>(rdr: R) -> Result<(T, PduHeader)>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.read_whole(|de| {
+        let header = de.read_any_pdu_header()?;
+        Ok((T::deserialize(de)?, header))
+    })
+}
+
+/// Deserialize a `bser` value framed as a Watchman PDU of either version from
+/// a byte slice, returning it along with the PDU header.
+pub fn from_slice_pdu_any<'de, T>(v: &'de [u8]) -> Result<(T, PduHeader)>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.read_whole(|de| {
+        let header = de.read_any_pdu_header()?;
+        Ok((T::deserialize(de)?, header))
+    })
+}
+
+/// Read the bytes of one Watchman PDU of either version from an `io::Read`,
+/// header and all, without decoding its body.
+///
+/// This separates framing from decoding: the PDU can be handed off and
+/// decoded later with `from_slice_pdu_any`. The reader is read from exactly
+/// as far as the end of the PDU, so passing `&mut reader` leaves it
+/// positioned at the start of the next one. `None` is returned if the reader
+/// ends before the first byte of a PDU, while one which ends partway through
+/// a PDU fails with `Error::Eof`.
+#[cfg(feature = "std")]
+pub fn read_pdu_bytes<R>(mut rdr: R) -> Result<Option<Vec<u8>>>
+where
+    R: io::Read,
+{
+    let mut pdu = vec![0; 2];
+    loop {
+        match io::Read::read(&mut rdr, &mut pdu[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    io::Read::read_exact(&mut rdr, &mut pdu[1..])?;
+    let caps_len = match [pdu[0], pdu[1]] {
+        pdu::MAGIC_V1 => 0,
+        pdu::MAGIC_V2 => 4,
+        _ => return Err(Error::InvalidMagic),
+    };
+
+    // Read the capabilities and the tag of the length, whose width is then
+    // known. Anything other than an integer tag is left for the deserializer
+    // to report.
+    pdu.resize(2 + caps_len + 1, 0);
+    io::Read::read_exact(&mut rdr, &mut pdu[2..])?;
+    let width = match pdu[pdu.len() - 1] {
+        0x03 => 1,
+        0x04 => 2,
+        0x05 => 4,
+        0x06 => 8,
+        _ => 0,
+    };
+    let header_len = pdu.len() + width;
+    pdu.resize(header_len, 0);
+    io::Read::read_exact(&mut rdr, &mut pdu[header_len - width..])?;
+
+    let body_len = Deserializer::from_slice(&pdu)
+        .read_any_pdu_header()?
+        .body_len;
+    let total = header_len
+        .checked_add(body_len)
+        .ok_or(Error::IntegerOverflow)?;
+    // The body grows as it arrives, rather than trusting its declared length
+    // for an allocation up front.
+    pdu.reserve(cmp::min(body_len, MAX_PREALLOC));
+    io::Read::read_to_end(&mut io::Read::take(&mut rdr, body_len as u64), &mut pdu)?;
+    if pdu.len() != total {
+        return Err(Error::Eof);
+    }
+    Ok(Some(pdu))
+}
+
+/// Render a BSER value as indented text which shows the tag that each part
+/// of it was encoded with, such as `Int8(43)` or `Templated["name"] [...]`.
+///
+/// This is intended for debugging, for example to find out why a value
+/// round-trips to different bytes, and needs no type to deserialize into.
+/// If the input is a Watchman PDU, its header is described on the first
+/// line. The format of the text may change between releases.
+pub fn dump_to_string(v: &[u8]) -> Result<String> {
+    let mut de = Deserializer::native(SliceRead::new(v));
+    de.read_whole(|de| {
+        let mut out = String::new();
+        if v.starts_with(&pdu::MAGIC_V2) {
+            let (caps, len) = de.read_pdu_header_v2()?;
+            out.push_str(&format!(
+                "PDU v2, capabilities {:#x}, {} bytes\n",
+                caps.bits(),
+                len
+            ));
+        } else if v.starts_with(&pdu::MAGIC_V1) {
+            let len = de.read_pdu_header()?;
+            out.push_str(&format!("PDU v1, {} bytes\n", len));
+        }
+        de.dump_value(&mut out, 0)?;
+        Ok(out)
+    })
+}
+
+/// Iterate over the back-to-back Watchman PDUs read from an `io::Read`.
+#[cfg(feature = "std")]
+pub fn stream_from_reader<R, T>(rdr: R) -> StreamDeserializer<'static, IoRead<R>, T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    Deserializer::native(IoRead::new(rdr)).into_iter()
+}
+
+/// Transcode a Watchman PDU read from an `io::Read` into JSON written to an
+/// `io::Write`, without deserializing it into any particular type.
+///
+/// BSER strings are not required to be UTF-8. When `lossy` is set, invalid
+/// sequences are replaced with U+FFFD; otherwise they fail the transcode.
+///
+/// This is a thin wrapper around `serde_transcode`, which works with any
+/// `Deserializer` once `Deserializer::set_string_mode` has been used to make
+/// strings visit as text.
+#[cfg(feature = "json")]
+pub fn transcode_to_json_writer<R, W>(rdr: R, writer: W, lossy: bool) -> Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let mut de = Deserializer::native(IoRead::new(rdr));
+    de.set_string_mode(if lossy {
+        StringMode::Utf8Lossy
+    } else {
+        StringMode::Utf8
+    });
+    de.read_whole(|de| {
+        de.read_pdu_header()?;
+        let mut ser = serde_json::Serializer::new(writer);
+        serde_transcode::transcode(&mut *de, &mut ser).map_err(|err| {
+            if err.is_io() {
+                Error::from(io::Error::from(err))
+            } else {
+                Error::Message(err.to_string())
+            }
+        })
+    })
 }
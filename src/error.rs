@@ -1,26 +1,338 @@
-use std;
-use std::fmt::{self, Display};
-use std::io;
-use std::str::Utf8Error;
+use crate::io;
 
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display};
+use core::mem;
+use core::result;
+use core::str::Utf8Error;
 use serde::{de, ser};
 
-pub type Result<T> = std::result::Result<T, Error>;
+// `core::error::Error` is only stable from Rust 1.81, so builds with `std`
+// keep using the `std` trait.
+#[cfg(not(feature = "std"))]
+use core::error::Error as StdError;
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
+
+pub type Result<T> = result::Result<T, Error>;
 
 // This is a bare-bones implementation. A real library would provide additional
-// information in its error type, for example the line and column at which the
-// error occurred, the byte offset into the input, or the current key being
-// processed.
+// information in its error type, for example the current key being processed.
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+    /// The input ended partway through a value, so more input may complete
+    /// it. Readers which fail with `io::ErrorKind::UnexpectedEof` produce
+    /// this rather than `Io`.
+    Eof,
     Message(String),
     Utf8Error(Utf8Error),
     TrailingBytes,
     IntegerOverflow,
+    /// A negative integer was read for an unsigned type.
+    NegativeUnsigned(i64),
+    /// An integer was read for a `char`, but it was not a Unicode scalar
+    /// value.
+    InvalidChar(i64),
+    /// A `Real` was read for an integer, but it had a fractional part or
+    /// was out of range.
+    LossyNumberConversion,
+    /// An array was read for a tuple, or a fixed-size array, but it held a
+    /// different number of elements.
+    TupleLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
     LengthRequired,
     NonStringKey,
-    MalformedTag,
+    /// Bytes which are not valid UTF-8 were serialized as a string, or
+    /// converted to JSON, while UTF-8 strings were required.
+    NonUtf8String,
+    /// A NaN or infinite float was serialized while they were rejected, or
+    /// converted to JSON.
+    NonFiniteFloat,
+    /// A tag byte outside of those defined by the BSER spec, perhaps from a
+    /// newer version of Watchman. The spec gives no way of finding the length
+    /// of such a value, so it can't be skipped, even by `IgnoredAny`.
+    UnknownTag(u8),
+    /// A key of an object, or of a template, was not a string.
+    NonStringKeyInObject,
+    InvalidMagic,
+    /// The body of a PDU was not the length declared in its header.
+    PduLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    LengthLimitExceeded,
+    /// A string was longer than `ReadLimit::max_string_len` allows.
+    StringTooLong {
+        len: usize,
+        limit: usize,
+    },
+    /// An array or object declared more elements than there are bytes left
+    /// in the input.
+    LengthExceedsInput,
+    RecursionLimitExceeded,
+    /// The input was larger than `ReadLimit::max_total_bytes` allows.
+    TotalBytesLimitExceeded,
+    /// An object held the same key more than once. The key is lossily
+    /// converted to UTF-8.
+    DuplicateKey(String),
+    /// A `Missing` tag appeared outside of the rows of a templated array.
+    UnexpectedMissing,
+    /// Another error, raised after `offset` bytes of the input had been
+    /// consumed.
+    At {
+        offset: usize,
+        error: Box<Error>,
+    },
+    /// An `Eof` raised partway through a fixed-width number, such as the
+    /// body of an `Int32`. `what` describes the number, for example "a 4-byte
+    /// integer". `inner` returns the `Eof` itself, so this is still
+    /// recognised as truncated input.
+    Reading {
+        what: &'static str,
+        error: Box<Error>,
+    },
+    /// A `Message` error raised while deserializing the value at `path`, a
+    /// JSON pointer such as `/files/3/name`. Only produced when
+    /// `Deserializer::set_detailed_errors` is enabled.
+    Path {
+        path: String,
+        error: Box<Error>,
+    },
+}
+
+/// Broad categories of `Error`, as returned by `Error::category`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Category {
+    /// The underlying reader or writer failed.
+    Io,
+    /// The input was not valid BSER, or ended partway through a value.
+    Syntax,
+    /// The input was valid BSER, but didn't match the type being
+    /// deserialized, or a value couldn't be represented in BSER.
+    Data,
+}
+
+impl Error {
+    /// Classify this error.
+    pub fn category(&self) -> Category {
+        match self {
+            Error::Io(_) => Category::Io,
+            Error::Message(_)
+            | Error::Utf8Error(_)
+            | Error::IntegerOverflow
+            | Error::NegativeUnsigned(_)
+            | Error::InvalidChar(_)
+            | Error::LossyNumberConversion
+            | Error::TupleLengthMismatch { .. }
+            | Error::LengthRequired
+            | Error::NonStringKey
+            | Error::NonUtf8String
+            | Error::NonFiniteFloat => Category::Data,
+            Error::Eof
+            | Error::TrailingBytes
+            | Error::UnknownTag(_)
+            | Error::NonStringKeyInObject
+            | Error::InvalidMagic
+            | Error::PduLengthMismatch { .. }
+            | Error::LengthLimitExceeded
+            | Error::StringTooLong { .. }
+            | Error::LengthExceedsInput
+            | Error::RecursionLimitExceeded
+            | Error::TotalBytesLimitExceeded
+            | Error::DuplicateKey(_)
+            | Error::UnexpectedMissing => Category::Syntax,
+            Error::At { error, .. } | Error::Reading { error, .. } | Error::Path { error, .. } => {
+                error.category()
+            }
+        }
+    }
+
+    /// Whether the underlying reader or writer failed. Such errors may be
+    /// worth retrying.
+    pub fn is_io(&self) -> bool {
+        self.category() == Category::Io
+    }
+
+    /// Whether the input was not valid BSER.
+    pub fn is_syntax(&self) -> bool {
+        self.category() == Category::Syntax
+    }
+
+    /// Whether the input ended partway through a value, as opposed to being
+    /// malformed. Reading again once more input is available may succeed.
+    pub fn is_eof(&self) -> bool {
+        *self.inner() == Error::Eof
+    }
+
+    /// Whether the data didn't match the type being deserialized, or couldn't
+    /// be serialized.
+    pub fn is_data(&self) -> bool {
+        self.category() == Category::Data
+    }
+
+    /// Attach an input offset to an error, unless it already has one.
+    pub(crate) fn at(offset: usize, error: Error) -> Self {
+        match error {
+            Error::At { .. } => error,
+            error => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(offset, %error, "bser error");
+                Error::At {
+                    offset,
+                    error: Box::new(error),
+                }
+            }
+        }
+    }
+
+    /// Note that an `Eof` was raised while reading `what`, a fixed-width
+    /// number. Other errors are returned as they are.
+    pub(crate) fn reading(what: &'static str, error: Error) -> Self {
+        match error {
+            Error::Eof => Error::Reading {
+                what,
+                error: Box::new(error),
+            },
+            error => error,
+        }
+    }
+
+    /// The offset into the input at which this error was raised, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Error::At { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// The path to the value whose deserialization raised this error, if
+    /// known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::At { error, .. } => error.path(),
+            Error::Path { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Prefix the path of a `Message` error with `segment`, which is already
+    /// escaped. Other errors have an offset, which locates them well enough.
+    pub(crate) fn in_path(self, segment: &str) -> Self {
+        match self {
+            Error::Message(_) => Error::Path {
+                path: segment.to_string(),
+                error: Box::new(self),
+            },
+            Error::Path { mut path, error } => {
+                path.insert_str(0, segment);
+                Error::Path { path, error }
+            }
+            error => error,
+        }
+    }
+
+    /// The underlying error, without its offset, path or other context.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::At { error, .. } | Error::Reading { error, .. } => error.inner(),
+            Error::Path { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Consume this error, returning the underlying error without its
+    /// offset, path or other context.
+    pub fn into_inner(self) -> Error {
+        match self {
+            Error::At { error, .. } | Error::Reading { error, .. } => error.into_inner(),
+            Error::Path { error, .. } => *error,
+            error => error,
+        }
+    }
+}
+
+/// Errors are compared structurally, except that `Io` errors are equal if
+/// they have the same `io::ErrorKind`, regardless of their message or
+/// source.
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        match (self, other) {
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (Error::Message(a), Error::Message(b)) => a == b,
+            (Error::DuplicateKey(a), Error::DuplicateKey(b)) => a == b,
+            (Error::NegativeUnsigned(a), Error::NegativeUnsigned(b)) => a == b,
+            (Error::InvalidChar(a), Error::InvalidChar(b)) => a == b,
+            (Error::UnknownTag(a), Error::UnknownTag(b)) => a == b,
+            (Error::Utf8Error(a), Error::Utf8Error(b)) => a == b,
+            (
+                Error::PduLengthMismatch {
+                    expected: a,
+                    actual: a_actual,
+                },
+                Error::PduLengthMismatch {
+                    expected: b,
+                    actual: b_actual,
+                },
+            ) => a == b && a_actual == b_actual,
+            (
+                Error::TupleLengthMismatch {
+                    expected: a,
+                    actual: a_actual,
+                },
+                Error::TupleLengthMismatch {
+                    expected: b,
+                    actual: b_actual,
+                },
+            ) => a == b && a_actual == b_actual,
+            (
+                Error::StringTooLong {
+                    len: a,
+                    limit: a_limit,
+                },
+                Error::StringTooLong {
+                    len: b,
+                    limit: b_limit,
+                },
+            ) => a == b && a_limit == b_limit,
+            (
+                Error::At {
+                    offset: a,
+                    error: a_err,
+                },
+                Error::At {
+                    offset: b,
+                    error: b_err,
+                },
+            ) => a == b && a_err == b_err,
+            (
+                Error::Reading {
+                    what: a,
+                    error: a_err,
+                },
+                Error::Reading {
+                    what: b,
+                    error: b_err,
+                },
+            ) => a == b && a_err == b_err,
+            (
+                Error::Path {
+                    path: a,
+                    error: a_err,
+                },
+                Error::Path {
+                    path: b,
+                    error: b_err,
+                },
+            ) => a == b && a_err == b_err,
+            // Any variant which carries data must be matched above, as this
+            // only compares which variant each error is.
+            (a, b) => mem::discriminant(a) == mem::discriminant(b),
+        }
+    }
 }
 
 impl ser::Error for Error {
@@ -35,7 +347,20 @@ impl de::Error for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Utf8Error(err) => Some(err),
+            // These only add context, so the cause is that of the inner error,
+            // whose message is already included in ours.
+            Error::At { error, .. } | Error::Reading { error, .. } | Error::Path { error, .. } => {
+                error.source()
+            }
+            _ => None,
+        }
+    }
+}
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -43,18 +368,60 @@ impl Display for Error {
             Error::Io(err) => err.fmt(f),
             Error::Message(err) => err.fmt(f),
             Error::Utf8Error(err) => err.fmt(f),
+            Error::Eof => "unexpected end of input".fmt(f),
             Error::TrailingBytes => "trailing bytes".fmt(f),
             Error::IntegerOverflow => "integer overflow".fmt(f),
+            Error::NegativeUnsigned(v) => {
+                write!(
+                    f,
+                    "integer `{}` is negative, expected an unsigned integer",
+                    v
+                )
+            }
+            Error::InvalidChar(v) => write!(f, "integer `{}` is not a valid char", v),
+            Error::LossyNumberConversion => "lossy number conversion".fmt(f),
+            Error::TupleLengthMismatch { expected, actual } => write!(
+                f,
+                "tuple length mismatch: expected {} elements, found {}",
+                expected, actual
+            ),
             Error::LengthRequired => "length required".fmt(f),
             Error::NonStringKey => "non string key".fmt(f),
-            Error::MalformedTag => "malformed tag".fmt(f),
+            Error::NonUtf8String => "string is not valid UTF-8".fmt(f),
+            Error::NonFiniteFloat => "float is not finite".fmt(f),
+            Error::UnknownTag(tag) => write!(f, "unknown tag {:#04x}", tag),
+            Error::NonStringKeyInObject => "object key is not a string".fmt(f),
+            Error::InvalidMagic => "invalid pdu magic".fmt(f),
+            Error::PduLengthMismatch { expected, actual } => write!(
+                f,
+                "pdu length mismatch: expected {} bytes, found {}",
+                expected, actual
+            ),
+            Error::LengthLimitExceeded => "length limit exceeded".fmt(f),
+            Error::StringTooLong { len, limit } => {
+                write!(f, "string of {} bytes exceeds limit of {}", len, limit)
+            }
+            Error::LengthExceedsInput => "length exceeds remaining input".fmt(f),
+            Error::RecursionLimitExceeded => "recursion limit exceeded".fmt(f),
+            Error::TotalBytesLimitExceeded => "total bytes limit exceeded".fmt(f),
+            Error::DuplicateKey(key) => write!(f, "duplicate key `{}`", key),
+            Error::UnexpectedMissing => {
+                "unexpected missing value, which is only valid within a templated array".fmt(f)
+            }
+            Error::At { offset, error } => write!(f, "{} at byte offset {}", error, offset),
+            Error::Reading { what, error } => write!(f, "{} while reading {}", error, what),
+            Error::Path { path, error } => write!(f, "at {}: {}", path, error),
         }
     }
 }
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
-        Error::Io(err)
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            Error::Eof
+        } else {
+            Error::Io(err)
+        }
     }
 }
 
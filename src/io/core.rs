@@ -0,0 +1,251 @@
+//! Minimal replacements for the parts of `std::io` used by this crate.
+
+use alloc::vec::Vec;
+use byteorder::ByteOrder;
+use core::fmt;
+use core::result;
+
+/// The kind of an I/O error, mirroring a subset of `std::io::ErrorKind`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The input ended before a complete value was read.
+    UnexpectedEof,
+    /// A write wrote no bytes, so the output can accept no more.
+    WriteZero,
+    /// The operation was interrupted, and may be retried.
+    Interrupted,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::UnexpectedEof => "unexpected end of file",
+            ErrorKind::WriteZero => "write zero",
+            ErrorKind::Interrupted => "operation interrupted",
+            ErrorKind::Other => "other error",
+        }
+    }
+}
+
+/// An I/O error, mirroring `std::io::Error`.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+}
+
+impl Error {
+    /// The kind of this error.
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl From<ErrorKind> for Error {
+    #[inline]
+    fn from(kind: ErrorKind) -> Self {
+        Error { kind }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.as_str().fmt(f)
+    }
+}
+
+impl core::error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// A source of bytes, mirroring `std::io::Read`.
+pub trait Read {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: ?Sized + Read> Read for &mut R {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl Read for &[u8] {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let amt = core::cmp::min(buf.len(), self.len());
+        let (a, b) = self.split_at(amt);
+        buf[..amt].copy_from_slice(a);
+        *self = b;
+        Ok(amt)
+    }
+}
+
+/// A sink for bytes, mirroring `std::io::Write`.
+pub trait Write {
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    fn flush(&mut self) -> Result<()>;
+
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => buf = &buf[n..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: ?Sized + Write> Write for &mut W {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+impl Write for Vec<u8> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The subset of `byteorder::ReadBytesExt` used by this crate, which is only
+/// available for `std::io::Read`.
+pub(crate) trait ReadBytesExt: Read {
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[inline]
+    fn read_i8(&mut self) -> Result<i8> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    #[inline]
+    fn read_i16<B: ByteOrder>(&mut self) -> Result<i16> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_i16(&buf))
+    }
+
+    #[inline]
+    fn read_i32<B: ByteOrder>(&mut self) -> Result<i32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_i32(&buf))
+    }
+
+    #[inline]
+    fn read_u32<B: ByteOrder>(&mut self) -> Result<u32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u32(&buf))
+    }
+
+    #[inline]
+    fn read_i64<B: ByteOrder>(&mut self) -> Result<i64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_i64(&buf))
+    }
+
+    #[inline]
+    fn read_f64<B: ByteOrder>(&mut self) -> Result<f64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_f64(&buf))
+    }
+}
+
+impl<R: ?Sized + Read> ReadBytesExt for R {}
+
+/// The subset of `byteorder::WriteBytesExt` used by this crate, which is only
+/// available for `std::io::Write`.
+pub(crate) trait WriteBytesExt: Write {
+    #[inline]
+    fn write_u8(&mut self, n: u8) -> Result<()> {
+        self.write_all(&[n])
+    }
+
+    #[inline]
+    fn write_i8(&mut self, n: i8) -> Result<()> {
+        self.write_all(&[n as u8])
+    }
+
+    #[inline]
+    fn write_i16<B: ByteOrder>(&mut self, n: i16) -> Result<()> {
+        let mut buf = [0; 2];
+        B::write_i16(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    #[inline]
+    fn write_i32<B: ByteOrder>(&mut self, n: i32) -> Result<()> {
+        let mut buf = [0; 4];
+        B::write_i32(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    #[inline]
+    fn write_u32<B: ByteOrder>(&mut self, n: u32) -> Result<()> {
+        let mut buf = [0; 4];
+        B::write_u32(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    #[inline]
+    fn write_i64<B: ByteOrder>(&mut self, n: i64) -> Result<()> {
+        let mut buf = [0; 8];
+        B::write_i64(&mut buf, n);
+        self.write_all(&buf)
+    }
+
+    #[inline]
+    fn write_f64<B: ByteOrder>(&mut self, n: f64) -> Result<()> {
+        let mut buf = [0; 8];
+        B::write_f64(&mut buf, n);
+        self.write_all(&buf)
+    }
+}
+
+impl<W: ?Sized + Write> WriteBytesExt for W {}
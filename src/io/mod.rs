@@ -0,0 +1,20 @@
+//! A tiny, `no_std`-friendly facade around `std::io`.
+//!
+//! With the `std` feature enabled this re-exports the types from `std::io`.
+//! Otherwise it provides the small subset of them which this crate needs to
+//! read from byte slices and write to `Vec<u8>`.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(feature = "std")]
+pub(crate) use byteorder::{ReadBytesExt, WriteBytesExt};
+
+#[cfg(not(feature = "std"))]
+mod core;
+
+#[cfg(not(feature = "std"))]
+pub use self::core::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use self::core::{ReadBytesExt, WriteBytesExt};
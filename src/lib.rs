@@ -1,8 +1,26 @@
 //! Serde support for the BSER Binary Protocol supported by Watchman
+//!
+//! The default `std` feature may be disabled to build against `core` and
+//! `alloc` alone. Values can then still be serialized into a `Vec<u8>` and
+//! deserialized from a byte slice. Building without `std` needs Rust 1.81 or
+//! later, for `core::error::Error`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 pub mod error;
+pub mod io;
 pub mod ser;
 pub mod de;
+pub mod pdu;
+pub mod value;
+
+#[cfg(feature = "tokio")]
+pub mod codec;
+
+#[cfg(feature = "testing")]
+pub mod testing;
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
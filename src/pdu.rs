@@ -0,0 +1,107 @@
+//! Framing for the PDUs exchanged with Watchman.
+//!
+//! Every PDU begins with a two byte magic value identifying the version of
+//! the protocol, followed by a BSER integer holding the length of the body.
+//! Version 2 PDUs additionally carry a 32-bit capabilities word between the
+//! magic and the length.
+
+use core::ops;
+
+/// Magic bytes which begin every BSER v1 PDU.
+pub(crate) const MAGIC_V1: [u8; 2] = [0x00, 0x01];
+
+/// Magic bytes which begin every BSER v2 PDU.
+pub(crate) const MAGIC_V2: [u8; 2] = [0x00, 0x02];
+
+/// The header of a PDU of either version, as read by
+/// `Deserializer::read_any_pdu_header`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct PduHeader {
+    /// The version of the protocol, `1` or `2`.
+    pub version: u8,
+    /// The capabilities carried by a v2 header, or `None` for v1.
+    pub capabilities: Option<Capabilities>,
+    /// The length of the body which follows the header.
+    pub body_len: usize,
+}
+
+/// Capability bits carried in the header of a BSER v2 PDU.
+///
+/// Bits which this crate doesn't know about are preserved rather than
+/// rejected, so capabilities sent by newer Watchman builds survive a
+/// round-trip.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Strings in the body are not guaranteed to be valid UTF-8.
+    pub const DISABLE_UNICODE: Capabilities = Capabilities(0x1);
+
+    /// Error messages in the body are not guaranteed to be valid UTF-8.
+    pub const DISABLE_UNICODE_FOR_ERRORS: Capabilities = Capabilities(0x2);
+
+    /// A set with no capabilities.
+    #[inline]
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    /// Construct a set from raw bits, keeping any unknown bits.
+    #[inline]
+    pub const fn from_bits(bits: u32) -> Self {
+        Capabilities(bits)
+    }
+
+    /// The raw bits of this set.
+    #[inline]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if every capability in `other` is also in `self`.
+    #[inline]
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: Capabilities) {
+        self.0 |= other.0;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, other: Capabilities) {
+        self.0 &= !other.0;
+    }
+}
+
+impl ops::BitOr for Capabilities {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, other: Self) -> Self {
+        Capabilities(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for Capabilities {
+    #[inline]
+    fn bitor_assign(&mut self, other: Self) {
+        self.insert(other)
+    }
+}
+
+impl ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, other: Self) -> Self {
+        Capabilities(self.0 & other.0)
+    }
+}
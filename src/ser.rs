@@ -1,10 +1,15 @@
 use crate::error::{Error, Result};
+use crate::pdu::{self, Capabilities};
+use crate::value::RAW_VALUE_TOKEN;
 use crate::Tag;
 
-use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
+use crate::io::{self, WriteBytesExt};
+
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian};
+use core::marker::PhantomData;
+use core::str;
 use serde::ser;
-use std::io;
-use std::marker::PhantomData;
 
 /// Helper object for serializing Rust objects into BSER.
 pub struct Serializer<W, B = NativeEndian>
@@ -12,9 +17,37 @@ where
     B: ByteOrder,
 {
     writer: W,
+    config: Config,
     _marker: PhantomData<B>,
 }
 
+/// Options controlling how values are encoded, shared with the serializers
+/// used for buffering.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Config {
+    templating: bool,
+    unknown_lengths: bool,
+    compact_ints: bool,
+    sort_keys: bool,
+    require_utf8_strings: bool,
+    reject_non_finite: bool,
+    char_as_int: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            templating: false,
+            unknown_lengths: false,
+            compact_ints: true,
+            sort_keys: false,
+            require_utf8_strings: false,
+            reject_non_finite: false,
+            char_as_int: false,
+        }
+    }
+}
+
 impl<W> Serializer<W, NativeEndian>
 where
     W: io::Write,
@@ -25,6 +58,141 @@ where
     }
 }
 
+impl<W> Serializer<W, BigEndian>
+where
+    W: io::Write,
+{
+    /// Create a serializer which writes integers and reals in network byte
+    /// order, rather than the native order used by Watchman.
+    #[inline]
+    pub fn big_endian(writer: W) -> Self {
+        Self::new(writer)
+    }
+}
+
+/// A set of serializer options, which can be kept around and used to create
+/// any number of serializers configured the same way.
+///
+/// The byte order is chosen by the constructor, and the options by the same
+/// setters `Serializer` has, with the same defaults.
+///
+/// ```
+/// use serde_bser::ser::{to_vec_with, SerializerBuilder};
+///
+/// let builder = SerializerBuilder::big_endian().with_compact_ints(false);
+/// let bytes = to_vec_with(builder, &1).unwrap();
+/// assert_eq!(bytes, [0x06, 0, 0, 0, 0, 0, 0, 0, 1]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SerializerBuilder<B = NativeEndian>
+where
+    B: ByteOrder,
+{
+    config: Config,
+    _marker: PhantomData<B>,
+}
+
+impl SerializerBuilder<NativeEndian> {
+    /// Create a builder for serializers which write in native byte order.
+    #[inline]
+    pub fn native() -> Self {
+        Self::new()
+    }
+}
+
+impl SerializerBuilder<BigEndian> {
+    /// Create a builder for serializers which write in network byte order.
+    #[inline]
+    pub fn big_endian() -> Self {
+        Self::new()
+    }
+}
+
+impl<B> SerializerBuilder<B>
+where
+    B: ByteOrder,
+{
+    /// Create a builder with the default options.
+    #[inline]
+    pub fn new() -> Self {
+        SerializerBuilder {
+            config: Config::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// See `Serializer::with_templating`.
+    #[inline]
+    pub fn with_templating(mut self, templating: bool) -> Self {
+        self.config.templating = templating;
+        self
+    }
+
+    /// See `Serializer::with_unknown_lengths`.
+    #[inline]
+    pub fn with_unknown_lengths(mut self, unknown_lengths: bool) -> Self {
+        self.config.unknown_lengths = unknown_lengths;
+        self
+    }
+
+    /// See `Serializer::with_compact_ints`.
+    #[inline]
+    pub fn with_compact_ints(mut self, compact_ints: bool) -> Self {
+        self.config.compact_ints = compact_ints;
+        self
+    }
+
+    /// See `Serializer::with_sort_keys`.
+    #[inline]
+    pub fn with_sort_keys(mut self, sort_keys: bool) -> Self {
+        self.config.sort_keys = sort_keys;
+        self
+    }
+
+    /// See `Serializer::with_require_utf8_strings`.
+    #[inline]
+    pub fn with_require_utf8_strings(mut self, require_utf8_strings: bool) -> Self {
+        self.config.require_utf8_strings = require_utf8_strings;
+        self
+    }
+
+    /// See `Serializer::with_reject_non_finite`.
+    #[inline]
+    pub fn with_reject_non_finite(mut self, reject_non_finite: bool) -> Self {
+        self.config.reject_non_finite = reject_non_finite;
+        self
+    }
+
+    /// See `Serializer::with_char_as_int`.
+    #[inline]
+    pub fn with_char_as_int(mut self, char_as_int: bool) -> Self {
+        self.config.char_as_int = char_as_int;
+        self
+    }
+
+    /// Create a serializer with these options which writes into `writer`.
+    #[inline]
+    pub fn build<W>(self, writer: W) -> Serializer<W, B>
+    where
+        W: io::Write,
+    {
+        Serializer {
+            writer,
+            config: self.config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<B> Default for SerializerBuilder<B>
+where
+    B: ByteOrder,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<W, B> Serializer<W, B>
 where
     W: io::Write,
@@ -34,6 +202,117 @@ where
     pub fn new(writer: W) -> Self {
         Serializer {
             writer,
+            config: Config::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Write the entries of each map in the order of their keys, as a
+    /// `BTreeMap` would be, so that maps such as `HashMap` always produce
+    /// the same output.
+    ///
+    /// This buffers every entry of a map until its end, encoding each key
+    /// and value separately and then copying them to the output, so it
+    /// costs an allocation per entry and a sort per map. Structs are written
+    /// in the order of their fields as normal. The default is to write
+    /// entries in the order the map yields them.
+    #[inline]
+    pub fn with_sort_keys(mut self, sort_keys: bool) -> Self {
+        self.config.sort_keys = sort_keys;
+        self
+    }
+
+    /// Fail with `Error::NonUtf8String` when bytes given to `serialize_bytes`
+    /// are not valid UTF-8, so that every string in the output can be read
+    /// by consumers which only accept UTF-8. `&str` values are always valid,
+    /// so they are not checked. Off by default, as BSER strings may hold
+    /// arbitrary bytes.
+    #[inline]
+    pub fn with_require_utf8_strings(mut self, require_utf8_strings: bool) -> Self {
+        self.config.require_utf8_strings = require_utf8_strings;
+        self
+    }
+
+    /// Fail with `Error::NonFiniteFloat` when serializing a NaN or infinite
+    /// float, so that the output can be re-encoded as JSON, which has no way
+    /// to represent them. Off by default, as a `Real` may hold any `f64`.
+    #[inline]
+    pub fn with_reject_non_finite(mut self, reject_non_finite: bool) -> Self {
+        self.config.reject_non_finite = reject_non_finite;
+        self
+    }
+
+    /// Write each `char` as an integer holding its codepoint, rather than as
+    /// a one-character string. Map keys are always written as strings. The
+    /// deserializer reads a `char` from either form.
+    #[inline]
+    pub fn with_char_as_int(mut self, char_as_int: bool) -> Self {
+        self.config.char_as_int = char_as_int;
+        self
+    }
+
+    /// Write sequences of objects as templated arrays.
+    ///
+    /// This requires every sequence to be buffered until its end, so that
+    /// the keys of its elements are known. The template holds every key
+    /// which appears in any element, and elements which don't have one of
+    /// those keys, or whose value for it is `None`, leave its slot missing
+    /// rather than null. Sequences which contain anything
+    /// other than objects are written out as normal arrays, as are empty
+    /// sequences, which have no keys to template and are shorter that way.
+    #[inline]
+    pub fn with_templating(mut self, templating: bool) -> Self {
+        self.config.templating = templating;
+        self
+    }
+
+    /// Accept sequences and maps whose length isn't known up front.
+    ///
+    /// BSER writes the length of an array or object before its contents, so
+    /// these are buffered until their end and then written out along with
+    /// the number of elements. When disabled, they fail with
+    /// `Error::LengthRequired`.
+    #[inline]
+    pub fn with_unknown_lengths(mut self, unknown_lengths: bool) -> Self {
+        self.config.unknown_lengths = unknown_lengths;
+        self
+    }
+
+    /// Write each integer value with the smallest tag which can hold it.
+    ///
+    /// This is the default. When disabled, every integer value is written as
+    /// an `Int64`, which keeps the encoding of a value the same whatever its
+    /// magnitude. Lengths in headers are always written compactly.
+    #[inline]
+    pub fn with_compact_ints(mut self, compact_ints: bool) -> Self {
+        self.config.compact_ints = compact_ints;
+        self
+    }
+
+    /// Write `bytes` to the output verbatim, as a value.
+    ///
+    /// The bytes are trusted to hold exactly one complete BSER value, in the
+    /// byte order of this serializer. Nothing checks this, and anything else
+    /// leaves the output malformed.
+    #[inline]
+    pub fn serialize_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Unwrap the `Writer` from the `Serializer`.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Create a serializer with the same configuration which writes into a
+    /// fresh buffer.
+    #[inline]
+    fn buffer(&self) -> Serializer<Vec<u8>, B> {
+        Serializer {
+            writer: Vec::new(),
+            config: self.config,
             _marker: PhantomData,
         }
     }
@@ -44,30 +323,72 @@ where
         Ok(())
     }
 
+    #[inline]
+    fn write_string(&mut self, v: &[u8]) -> Result<()> {
+        if v.len() <= i8::MAX as usize {
+            // Short strings, such as the names of struct fields, are common
+            // enough that writing their header in one go is worthwhile.
+            self.writer
+                .write_all(&[Tag::String as u8, Tag::Int8 as u8, v.len() as u8])?;
+        } else {
+            self.write_tag(Tag::String)?;
+            self.serialize_usize(v.len())?;
+        }
+        self.writer.write_all(v)?;
+        Ok(())
+    }
+
     #[inline]
     fn serialize_usize(&mut self, v: usize) -> Result<()> {
-        ser::Serializer::serialize_u64(self, v as u64)
+        if v as u64 > i64::MAX as u64 {
+            return Err(Error::IntegerOverflow);
+        }
+        self.write_compact_int(v as i64)
     }
 
     #[inline]
     fn serialize_int(&mut self, v: i64) -> Result<()> {
+        if self.config.compact_ints {
+            self.write_compact_int(v)
+        } else {
+            self.write_tag(Tag::Int64)?;
+            self.writer.write_i64::<B>(v)?;
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn write_compact_int(&mut self, v: i64) -> Result<()> {
         // Find the smallest integer value we can write out
-        if (std::i8::MIN as i64) <= v && v <= (std::i8::MAX as i64) {
+        if (i8::MIN as i64) <= v && v <= (i8::MAX as i64) {
             self.write_tag(Tag::Int8)?;
             self.writer.write_i8(v as i8)?;
-        } else if (std::i16::MIN as i64) <= v && v <= (std::i16::MAX as i64) {
+        } else if (i16::MIN as i64) <= v && v <= (i16::MAX as i64) {
             self.write_tag(Tag::Int16)?;
             self.writer.write_i16::<B>(v as i16)?;
-        } else if (std::i32::MIN as i64) <= v && v <= (std::i32::MAX as i64) {
+        } else if (i32::MIN as i64) <= v && v <= (i32::MAX as i64) {
             self.write_tag(Tag::Int32)?;
             self.writer.write_i32::<B>(v as i32)?;
         } else {
             self.write_tag(Tag::Int64)?;
-            self.writer.write_i64::<B>(v as i64)?;
+            self.writer.write_i64::<B>(v)?;
         }
         Ok(())
     }
 
+    /// Write a Watchman PDU header for a body of `len` bytes.
+    fn write_pdu_header(&mut self, len: usize) -> Result<()> {
+        self.writer.write_all(&pdu::MAGIC_V1)?;
+        self.serialize_usize(len)
+    }
+
+    /// Write a Watchman v2 PDU header for a body of `len` bytes.
+    fn write_pdu_header_v2(&mut self, caps: Capabilities, len: usize) -> Result<()> {
+        self.writer.write_all(&pdu::MAGIC_V2)?;
+        self.writer.write_u32::<LittleEndian>(caps.bits())?;
+        self.serialize_usize(len)
+    }
+
     #[inline]
     fn begin_object(&mut self, size: usize) -> Result<()> {
         self.write_tag(Tag::Object)?;
@@ -79,6 +400,74 @@ where
         self.write_tag(Tag::Array)?;
         self.serialize_usize(size)
     }
+
+    /// Write out a buffered sequence. If every element is an object, it is
+    /// written as a templated array, otherwise as a normal array.
+    fn write_rows(&mut self, rows: &[Row]) -> Result<()> {
+        let objects = rows
+            .iter()
+            .map(|row| match row {
+                Row::Object(fields) => Some(fields),
+                Row::Other(_) => None,
+            })
+            .collect::<Option<Vec<_>>>();
+
+        let objects = match objects {
+            Some(objects) if !objects.is_empty() => objects,
+            _ => {
+                self.begin_array(rows.len())?;
+                for row in rows {
+                    self.write_row(row)?;
+                }
+                return Ok(());
+            }
+        };
+
+        // The template holds every key, in the order it was first seen.
+        let mut keys: Vec<&[u8]> = Vec::new();
+        for field in objects.iter().flat_map(|fields| fields.iter()) {
+            if !keys.contains(&&field.key[..]) {
+                keys.push(&field.key);
+            }
+        }
+
+        self.write_tag(Tag::Templated)?;
+        self.begin_array(keys.len())?;
+        for key in &keys {
+            self.writer.write_all(key)?;
+        }
+
+        self.serialize_usize(objects.len())?;
+        for fields in objects {
+            for key in &keys {
+                match fields.iter().find(|field| field.key == *key) {
+                    Some(Field {
+                        value: Some(value), ..
+                    }) => self.writer.write_all(value)?,
+                    _ => self.write_tag(Tag::Missing)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out a single buffered element with the normal encoding.
+    fn write_row(&mut self, row: &Row) -> Result<()> {
+        match row {
+            Row::Object(fields) => {
+                self.begin_object(fields.len())?;
+                for field in fields {
+                    self.writer.write_all(&field.key)?;
+                    match field.value {
+                        Some(ref value) => self.writer.write_all(value)?,
+                        None => self.write_tag(Tag::Null)?,
+                    }
+                }
+            }
+            Row::Other(bytes) => self.writer.write_all(bytes)?,
+        }
+        Ok(())
+    }
 }
 
 impl<'a, W, B> ser::Serializer for &'a mut Serializer<W, B>
@@ -89,11 +478,11 @@ where
     type Ok = ();
     type Error = Error;
 
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
+    type SerializeSeq = Compound<'a, W, B>;
+    type SerializeTuple = Compound<'a, W, B>;
+    type SerializeTupleStruct = Compound<'a, W, B>;
     type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
+    type SerializeMap = MapCompound<'a, W, B>;
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
 
@@ -143,12 +532,30 @@ where
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<()> {
-        if v > i64::max_value() as u64 {
+        if v > i64::MAX as u64 {
+            return Err(Error::IntegerOverflow);
+        }
+        self.serialize_int(v as i64)
+    }
+
+    #[inline]
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        if v < i64::MIN as i128 || v > i64::MAX as i128 {
+            return Err(Error::IntegerOverflow);
+        }
+        self.serialize_int(v as i64)
+    }
+
+    #[inline]
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        if v > i64::MAX as u128 {
             return Err(Error::IntegerOverflow);
         }
         self.serialize_int(v as i64)
     }
 
+    /// BSER has no single precision real, so this is widened to an `f64`.
+    /// Use `value::F32Bits` to keep the exact bits instead.
     #[inline]
     fn serialize_f32(self, v: f32) -> Result<()> {
         self.serialize_f64(v as f64)
@@ -156,6 +563,9 @@ where
 
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<()> {
+        if self.config.reject_non_finite && !v.is_finite() {
+            return Err(Error::NonFiniteFloat);
+        }
         self.write_tag(Tag::Real)?;
         self.writer.write_f64::<B>(v)?;
         Ok(())
@@ -163,21 +573,24 @@ where
 
     #[inline]
     fn serialize_char(self, v: char) -> Result<()> {
+        if self.config.char_as_int {
+            return self.serialize_int(v as i64);
+        }
         let mut buf = [0; 4];
         self.serialize_str(v.encode_utf8(&mut buf))
     }
 
     #[inline]
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.serialize_bytes(v.as_bytes())
+        self.write_string(v.as_bytes())
     }
 
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.write_tag(Tag::String)?;
-        self.serialize_usize(v.len())?;
-        self.writer.write(v)?;
-        Ok(())
+        if self.config.require_utf8_strings && str::from_utf8(v).is_err() {
+            return Err(Error::NonUtf8String);
+        }
+        self.write_string(v)
     }
 
     #[inline]
@@ -202,15 +615,18 @@ where
 
     /// Serialize newtypes without an object wrapper.
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
+        if name == RAW_VALUE_TOKEN {
+            return value.serialize(RawValueEmitter { ser: self });
+        }
         value.serialize(self)
     }
 
     #[inline]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -218,7 +634,7 @@ where
         value: &T,
     ) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         self.begin_object(1)?;
         self.serialize_str(variant)?;
@@ -232,21 +648,28 @@ where
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
 
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        if let Some(len) = len {
+        if len.is_none() && !self.config.unknown_lengths {
+            return Err(Error::LengthRequired);
+        }
+
+        let state = if self.config.templating {
+            State::Rows(Vec::with_capacity(len.unwrap_or(0)))
+        } else if let Some(len) = len {
             self.begin_array(len)?;
-            Ok(self)
+            State::Direct
         } else {
-            Err(Error::LengthRequired)
-        }
+            State::Counted(Counted::new(self))
+        };
+        Ok(Compound { ser: self, state })
     }
 
     #[inline]
@@ -273,22 +696,30 @@ where
     ) -> Result<Self::SerializeTupleVariant> {
         self.begin_object(1)?;
         self.serialize_str(variant)?;
-        self.serialize_seq(Some(len))
+        self.begin_array(len)?;
+        Ok(self)
     }
 
     #[inline]
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        if let Some(len) = len {
-            self.begin_object(len)?;
-            Ok(self)
-        } else {
-            Err(Error::LengthRequired)
-        }
+        let state = match len {
+            _ if len.is_none() && !self.config.unknown_lengths => {
+                return Err(Error::LengthRequired)
+            }
+            _ if self.config.sort_keys => MapState::Sorted(Vec::new()),
+            Some(len) => {
+                self.begin_object(len)?;
+                MapState::Direct
+            }
+            None => MapState::Counted(Counted::new(self)),
+        };
+        Ok(MapCompound { ser: self, state })
     }
 
     #[inline]
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+        self.begin_object(len)?;
+        Ok(self)
     }
 
     #[inline]
@@ -301,11 +732,69 @@ where
     ) -> Result<Self::SerializeStructVariant> {
         self.begin_object(1)?;
         self.serialize_str(variant)?;
-        self.serialize_map(Some(len))
+        self.begin_object(len)?;
+        Ok(self)
+    }
+
+    /// BSER is a binary format, so types such as `IpAddr` which have a
+    /// compact form should use it. Object keys are always strings, so they
+    /// are still serialized as human readable.
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// State for serializing the elements of a sequence.
+pub struct Compound<'a, W, B>
+where
+    B: ByteOrder,
+{
+    ser: &'a mut Serializer<W, B>,
+    state: State<B>,
+}
+
+/// How the elements of a sequence are written.
+enum State<B>
+where
+    B: ByteOrder,
+{
+    /// Directly, after the length of the sequence.
+    Direct,
+    /// Buffered until the end of the sequence, so that it can be written as a
+    /// templated array.
+    Rows(Vec<Row>),
+    /// Buffered until the end of the sequence, as its length wasn't known.
+    Counted(Counted<B>),
+}
+
+/// Elements of a sequence or entries of a map whose length wasn't known up
+/// front, buffered until their end.
+struct Counted<B>
+where
+    B: ByteOrder,
+{
+    ser: Serializer<Vec<u8>, B>,
+    len: usize,
+}
+
+impl<B> Counted<B>
+where
+    B: ByteOrder,
+{
+    #[inline]
+    fn new<W>(ser: &Serializer<W, B>) -> Self
+    where
+        W: io::Write,
+    {
+        Counted {
+            ser: ser.buffer(),
+            len: 0,
+        }
     }
 }
 
-impl<'a, W, B> ser::SerializeSeq for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeSeq for Compound<'_, W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -314,20 +803,38 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, v: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, v: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        v.serialize(&mut **self)
+        match &mut self.state {
+            State::Direct => v.serialize(&mut *self.ser),
+            State::Rows(rows) => {
+                rows.push(Row::capture(self.ser, v)?);
+                Ok(())
+            }
+            State::Counted(counted) => {
+                counted.len += 1;
+                v.serialize(&mut counted.ser)
+            }
+        }
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        match self.state {
+            State::Direct => Ok(()),
+            State::Rows(rows) => self.ser.write_rows(&rows),
+            State::Counted(counted) => {
+                self.ser.begin_array(counted.len)?;
+                self.ser.writer.write_all(&counted.ser.writer)?;
+                Ok(())
+            }
+        }
     }
 }
 
-impl<'a, W, B> ser::SerializeTuple for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeTuple for Compound<'_, W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -336,20 +843,20 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, v: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, v: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, v)
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, W, B> ser::SerializeTupleStruct for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeTupleStruct for Compound<'_, W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -358,20 +865,20 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, v: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, v: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, v)
     }
 
     #[inline]
     fn end(self) -> Result<()> {
-        Ok(())
+        ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, W, B> ser::SerializeTupleVariant for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeTupleVariant for &mut Serializer<W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -380,9 +887,9 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, v: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, v: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         v.serialize(&mut **self)
     }
@@ -393,7 +900,30 @@ where
     }
 }
 
-impl<'a, W, B> ser::SerializeMap for &'a mut Serializer<W, B>
+/// State for serializing the entries of a map.
+pub struct MapCompound<'a, W, B>
+where
+    B: ByteOrder,
+{
+    ser: &'a mut Serializer<W, B>,
+    state: MapState<B>,
+}
+
+/// How the entries of a map are written.
+enum MapState<B>
+where
+    B: ByteOrder,
+{
+    /// Directly, after the length of the map.
+    Direct,
+    /// Buffered until the end of the map, as its length wasn't known.
+    Counted(Counted<B>),
+    /// Buffered as encoded keys and values until the end of the map, so
+    /// that they can be sorted by key.
+    Sorted(Vec<(Vec<u8>, Vec<u8>)>),
+}
+
+impl<W, B> ser::SerializeMap for MapCompound<'_, W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -402,30 +932,73 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         // NOTE: Use a custom sub-serializer here to convert any keys to
         // strings, and reject other keys.
-        key.serialize(MapKeySerializer { ser: &mut **self })
+        match &mut self.state {
+            MapState::Direct => key.serialize(MapKeySerializer {
+                ser: &mut *self.ser,
+            }),
+            MapState::Counted(counted) => {
+                counted.len += 1;
+                key.serialize(MapKeySerializer {
+                    ser: &mut counted.ser,
+                })
+            }
+            MapState::Sorted(entries) => {
+                let mut buf = self.ser.buffer();
+                key.serialize(MapKeySerializer { ser: &mut buf })?;
+                entries.push((buf.writer, Vec::new()));
+                Ok(())
+            }
+        }
     }
 
     #[inline]
-    fn serialize_value<T: ?Sized>(&mut self, v: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, v: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        v.serialize(&mut **self)
+        match &mut self.state {
+            MapState::Direct => v.serialize(&mut *self.ser),
+            MapState::Counted(counted) => v.serialize(&mut counted.ser),
+            MapState::Sorted(entries) => {
+                let mut buf = self.ser.buffer();
+                v.serialize(&mut buf)?;
+                let entry = entries
+                    .last_mut()
+                    .expect("serialize_value called before serialize_key");
+                entry.1 = buf.writer;
+                Ok(())
+            }
+        }
     }
 
     #[inline]
     fn end(self) -> Result<()> {
+        match self.state {
+            MapState::Direct => {}
+            MapState::Counted(counted) => {
+                self.ser.begin_object(counted.len)?;
+                self.ser.writer.write_all(&counted.ser.writer)?;
+            }
+            MapState::Sorted(mut entries) => {
+                entries.sort_by(|(a, _), (b, _)| string_contents(a).cmp(string_contents(b)));
+                self.ser.begin_object(entries.len())?;
+                for (key, value) in entries {
+                    self.ser.writer.write_all(&key)?;
+                    self.ser.writer.write_all(&value)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
-impl<'a, W, B> ser::SerializeStruct for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeStruct for &mut Serializer<W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -434,11 +1007,10 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        // XXX(nika): This can probably do better!
         ser::Serializer::serialize_str(&mut **self, key)?;
         value.serialize(&mut **self)
     }
@@ -449,7 +1021,7 @@ where
     }
 }
 
-impl<'a, W, B> ser::SerializeStructVariant for &'a mut Serializer<W, B>
+impl<W, B> ser::SerializeStructVariant for &mut Serializer<W, B>
 where
     W: io::Write,
     B: ByteOrder,
@@ -458,9 +1030,9 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeStruct::serialize_field(self, key, value)
     }
@@ -485,9 +1057,8 @@ where
     B: ByteOrder,
 {
     fn serialize_int(self, value: impl itoa::Integer) -> Result<()> {
-        let mut bytes = [b'\0'; 20];
-        let n = itoa::write(&mut bytes[..], value)?;
-        ser::Serializer::serialize_bytes(self.ser, &bytes[..n])
+        let mut buffer = itoa::Buffer::new();
+        ser::Serializer::serialize_str(self.ser, buffer.format(value))
     }
 }
 
@@ -523,10 +1094,14 @@ where
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
+        // A raw value may hold anything, so it can't be trusted to be a key.
+        if name == RAW_VALUE_TOKEN {
+            return Err(Error::NonStringKey);
+        }
         value.serialize(self)
     }
 
@@ -566,6 +1141,14 @@ where
         self.serialize_int(value)
     }
 
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        self.serialize_int(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.serialize_int(value)
+    }
+
     fn serialize_f32(self, _value: f32) -> Result<()> {
         Err(Error::NonStringKey)
     }
@@ -575,7 +1158,8 @@ where
     }
 
     fn serialize_char(self, value: char) -> Result<()> {
-        self.ser.serialize_char(value)
+        let mut buf = [0; 4];
+        self.ser.serialize_str(value.encode_utf8(&mut buf))
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
@@ -592,7 +1176,7 @@ where
         Err(Error::NonStringKey)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -600,7 +1184,7 @@ where
         _value: &T,
     ) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         Err(Error::NonStringKey)
     }
@@ -609,9 +1193,9 @@ where
         Err(Error::NonStringKey)
     }
 
-    fn serialize_some<T: ?Sized>(self, _value: &T) -> Result<()>
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         Err(Error::NonStringKey)
     }
@@ -663,33 +1247,1039 @@ where
 
 // ----------------------------------------------------------------------------
 
-/// Serialize the given data structure as BSER into the IO stream.
-///
-/// # Errors
-///
-/// Serialization can fail if `T`'s implementation of `Serialize` decides to
-/// fail, or if `T` contains a map with non-string keys.
-pub fn to_writer<W, T: ?Sized>(writer: W, value: &T) -> Result<()>
+/// Helper serializer for the contents of a `RawValue`, which writes the
+/// bytes it is given verbatim and rejects anything else.
+struct RawValueEmitter<'a, W, B>
 where
-    W: io::Write,
-    T: ser::Serialize,
+    B: ByteOrder,
 {
-    let mut ser = Serializer::native(writer);
-    value.serialize(&mut ser)?;
-    Ok(())
+    ser: &'a mut Serializer<W, B>,
 }
 
-/// Serialize the given data structure as a BSER byte vector.
-///
-/// # Errors
-///
-/// Serialization can fail if `T`'s implementation of `Serialize` decides to
-/// fail, or if `T` contains a map with non-string keys.
-pub fn to_vec<T: ?Sized>(value: &T) -> Result<Vec<u8>>
+impl<W, B> RawValueEmitter<'_, W, B>
 where
-    T: ser::Serialize,
+    B: ByteOrder,
 {
-    let mut writer = Vec::with_capacity(128);
-    to_writer(&mut writer, value)?;
-    Ok(writer)
+    fn invalid<T>() -> Result<T> {
+        Err(ser::Error::custom("expected raw BSER bytes"))
+    }
+}
+
+impl<'a, W, B> ser::Serializer for RawValueEmitter<'a, W, B>
+where
+    W: io::Write,
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        self.ser.serialize_raw(value)
+    }
+
+    fn serialize_bool(self, _value: bool) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_i8(self, _value: i8) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_i16(self, _value: i16) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_i32(self, _value: i32) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_i64(self, _value: i64) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_u8(self, _value: u8) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_u16(self, _value: u16) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_u32(self, _value: u32) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_u64(self, _value: u64) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_char(self, _value: char) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_str(self, _value: &str) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Self::invalid()
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Self::invalid()
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Self::invalid()
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Self::invalid()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Self::invalid()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Self::invalid()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Self::invalid()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Self::invalid()
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Self::invalid()
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Self::invalid()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Self::invalid()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A buffered element of a sequence which may be written as a templated array.
+enum Row {
+    /// The encoded keys and values of an object.
+    Object(Vec<Field>),
+    /// The encoding of any other value.
+    Other(Vec<u8>),
+}
+
+struct Field {
+    key: Vec<u8>,
+    /// The encoded value, or `None` if the field is missing.
+    value: Option<Vec<u8>>,
+}
+
+impl Row {
+    fn capture<W, B, T>(ser: &Serializer<W, B>, value: &T) -> Result<Row>
+    where
+        W: io::Write,
+        B: ByteOrder,
+        T: ?Sized + ser::Serialize,
+    {
+        let mut other = ser.buffer();
+        let mut fields = None;
+        value.serialize(RowSerializer {
+            ser: &mut other,
+            fields: &mut fields,
+        })?;
+
+        Ok(match fields {
+            Some(fields) => Row::Object(fields),
+            None => Row::Other(other.writer),
+        })
+    }
+}
+
+/// Helper serializer for the elements of a sequence which may be templated.
+/// Objects have their fields captured individually, while any other value is
+/// written to `ser` as normal.
+struct RowSerializer<'a, B>
+where
+    B: ByteOrder,
+{
+    ser: &'a mut Serializer<Vec<u8>, B>,
+    fields: &'a mut Option<Vec<Field>>,
+}
+
+impl<'a, B> ser::Serializer for RowSerializer<'a, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, Vec<u8>, B>;
+    type SerializeTuple = Compound<'a, Vec<u8>, B>;
+    type SerializeTupleStruct = Compound<'a, Vec<u8>, B>;
+    type SerializeTupleVariant = &'a mut Serializer<Vec<u8>, B>;
+    type SerializeMap = RowObject<'a, B>;
+    type SerializeStruct = RowObject<'a, B>;
+    type SerializeStructVariant = &'a mut Serializer<Vec<u8>, B>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.ser.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.ser.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.ser.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.ser.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.ser.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.ser.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.ser.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.ser.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.ser.serialize_u64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.ser.serialize_i128(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.ser.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.ser.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.ser.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.ser.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.ser.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.ser.serialize_bytes(v)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.ser.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        self.ser.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.ser
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        // A raw value is written as it is, even if it holds an object.
+        if name == RAW_VALUE_TOKEN {
+            return self.ser.serialize_newtype_struct(name, value);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.ser
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.ser.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.ser.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.ser.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.ser.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.ser
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        // The fields are counted as they are captured, so the length is only
+        // needed up front if this serializer requires it.
+        if len.is_none() && !self.ser.config.unknown_lengths {
+            return Err(Error::LengthRequired);
+        }
+        Ok(RowObject {
+            ser: self.ser,
+            fields: self.fields.insert(Vec::with_capacity(len.unwrap_or(0))),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.ser
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Helper serializer for the value of a field within a row. A `None` value is
+/// recorded as missing rather than written, while any other value is written
+/// to `ser` as normal.
+struct FieldSerializer<'a, B>
+where
+    B: ByteOrder,
+{
+    ser: &'a mut Serializer<Vec<u8>, B>,
+    missing: &'a mut bool,
+}
+
+impl<'a, B> ser::Serializer for FieldSerializer<'a, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, Vec<u8>, B>;
+    type SerializeTuple = Compound<'a, Vec<u8>, B>;
+    type SerializeTupleStruct = Compound<'a, Vec<u8>, B>;
+    type SerializeTupleVariant = &'a mut Serializer<Vec<u8>, B>;
+    type SerializeMap = MapCompound<'a, Vec<u8>, B>;
+    type SerializeStruct = &'a mut Serializer<Vec<u8>, B>;
+    type SerializeStructVariant = &'a mut Serializer<Vec<u8>, B>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.ser.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.ser.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.ser.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.ser.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.ser.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.ser.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.ser.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.ser.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.ser.serialize_u64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.ser.serialize_i128(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.ser.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.ser.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.ser.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.ser.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.ser.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.ser.serialize_bytes(v)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.ser.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        self.ser.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.ser
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        if name == RAW_VALUE_TOKEN {
+            return self.ser.serialize_newtype_struct(name, value);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.ser
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        *self.missing = true;
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.ser.serialize_some(value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.ser.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.ser.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.ser.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.ser
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.ser.serialize_map(len)
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.ser.serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.ser
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The contents of an encoded string, after its tag and length.
+fn string_contents(encoded: &[u8]) -> &[u8] {
+    let width = match encoded[1] {
+        0x03 => 1,
+        0x04 => 2,
+        0x05 => 4,
+        _ => 8,
+    };
+    &encoded[2 + width..]
+}
+
+/// Captures the fields of an object within a sequence which may be templated.
+struct RowObject<'a, B>
+where
+    B: ByteOrder,
+{
+    ser: &'a mut Serializer<Vec<u8>, B>,
+    fields: &'a mut Vec<Field>,
+    key: Option<Vec<u8>>,
+}
+
+impl<B> ser::SerializeMap for RowObject<'_, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let mut buf = self.ser.buffer();
+        key.serialize(MapKeySerializer { ser: &mut buf })?;
+        self.key = Some(buf.writer);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, v: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        let mut buf = self.ser.buffer();
+        let mut missing = false;
+        v.serialize(FieldSerializer {
+            ser: &mut buf,
+            missing: &mut missing,
+        })?;
+        self.fields.push(Field {
+            key,
+            value: if missing { None } else { Some(buf.writer) },
+        });
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        if self.ser.config.sort_keys {
+            self.fields
+                .sort_by(|a, b| string_contents(&a.key).cmp(string_contents(&b.key)));
+        }
+        Ok(())
+    }
+}
+
+impl<B> ser::SerializeStruct for RowObject<'_, B>
+where
+    B: ByteOrder,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Writes values as Watchman PDUs, reusing one buffer for their bodies.
+///
+/// The length of a PDU body must be written before the body itself, so each
+/// body is serialized into a buffer first. `to_writer_pdu` allocates a fresh
+/// buffer for every call, while a `PduWriter` keeps its buffer, which grows
+/// to fit the largest body written so far.
+#[derive(Debug, Default)]
+pub struct PduWriter {
+    body: Vec<u8>,
+}
+
+impl PduWriter {
+    /// Create a new `PduWriter` with an empty buffer.
+    #[inline]
+    pub fn new() -> Self {
+        PduWriter { body: Vec::new() }
+    }
+
+    /// Create a new `PduWriter` whose buffer can hold a body of `capacity`
+    /// bytes without reallocating.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        PduWriter {
+            body: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Serialize the given data structure as a Watchman PDU into the IO
+    /// stream.
+    ///
+    /// The writer is flushed once the whole PDU has been written.
+    ///
+    /// # Errors
+    ///
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides
+    /// to fail, or if `T` contains a map with non-string keys.
+    pub fn write_pdu<W, T>(&mut self, writer: W, value: &T) -> Result<()>
+    where
+        W: io::Write,
+        T: ?Sized + ser::Serialize,
+    {
+        self.serialize_body(value)?;
+        let mut ser = Serializer::native(writer);
+        ser.write_pdu_header(self.body.len())?;
+        ser.writer.write_all(&self.body)?;
+        ser.writer.flush()?;
+        Ok(())
+    }
+
+    /// Serialize the given data structure as a Watchman v2 PDU carrying the
+    /// given capabilities into the IO stream.
+    ///
+    /// The writer is flushed once the whole PDU has been written.
+    ///
+    /// # Errors
+    ///
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides
+    /// to fail, or if `T` contains a map with non-string keys.
+    pub fn write_pdu_v2<W, T>(&mut self, writer: W, value: &T, caps: Capabilities) -> Result<()>
+    where
+        W: io::Write,
+        T: ?Sized + ser::Serialize,
+    {
+        self.serialize_body(value)?;
+        let mut ser = Serializer::native(writer);
+        ser.write_pdu_header_v2(caps, self.body.len())?;
+        ser.writer.write_all(&self.body)?;
+        ser.writer.flush()?;
+        Ok(())
+    }
+
+    fn serialize_body<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.body.clear();
+        to_writer(&mut self.body, value)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Serialize the given data structure as BSER into the IO stream.
+///
+/// Nothing is buffered: sequences and maps, whose lengths must be known up
+/// front, are written to the stream element by element as they are
+/// serialized, so even a huge collection takes no more memory than its
+/// largest element does.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let mut ser = Serializer::native(writer);
+    value.serialize(&mut ser)?;
+    Ok(())
+}
+
+/// Serialize the given data structure as a BSER byte vector.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer(&mut writer, value)?;
+    Ok(writer)
+}
+
+/// Serialize the given data structure as a BSER byte vector, allocated once
+/// at exactly the length of the encoding.
+///
+/// The value is serialized twice: once to find the length, by way of
+/// `serialized_size`, and again into the vector. This costs more time than
+/// `to_vec`, but none of the reallocations and copies that `to_vec` makes as
+/// a large encoding grows.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec_exact<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(serialized_size(value)?);
+    to_writer(&mut writer, value)?;
+    Ok(writer)
+}
+
+/// Serialize the given data structure as BSER into the IO stream, using the
+/// byte order and options of `builder`.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_writer_with<W, T, B>(builder: SerializerBuilder<B>, writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+    B: ByteOrder,
+{
+    let mut ser = builder.build(writer);
+    value.serialize(&mut ser)?;
+    Ok(())
+}
+
+/// Serialize the given data structure as a BSER byte vector, using the byte
+/// order and options of `builder`.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec_with<T, B>(builder: SerializerBuilder<B>, value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+    B: ByteOrder,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_with(builder, &mut writer, value)?;
+    Ok(writer)
+}
+
+/// Compute the length of the BSER encoding of the given data structure,
+/// without keeping the encoded bytes.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut counter = ByteCounter(0);
+    to_writer(&mut counter, value)?;
+    Ok(counter.0)
+}
+
+/// A writer which discards its input, counting the bytes written to it.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serialize the given data structure as BSER in network byte order into the
+/// IO stream.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_writer_be<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let mut ser = Serializer::big_endian(writer);
+    value.serialize(&mut ser)?;
+    Ok(())
+}
+
+/// Serialize the given data structure as a BSER byte vector in network byte
+/// order.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec_be<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_be(&mut writer, value)?;
+    Ok(writer)
+}
+
+/// Serialize the given data structure as a Watchman PDU into the IO stream.
+///
+/// The length of the body must be written before the body itself, so the
+/// body is first serialized into an internal buffer.
+///
+/// The writer is flushed once the whole PDU has been written, so that a
+/// request written to a buffered stream, such as a `BufWriter` around a
+/// socket, is sent rather than left waiting in the buffer.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_writer_pdu<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let body = to_vec(value)?;
+    let mut ser = Serializer::native(writer);
+    ser.write_pdu_header(body.len())?;
+    ser.writer.write_all(&body)?;
+    ser.writer.flush()?;
+    Ok(())
+}
+
+/// Serialize the given data structure as a Watchman PDU byte vector.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec_pdu<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let body = to_vec(value)?;
+    let mut ser = Serializer::native(Vec::with_capacity(body.len() + 11));
+    ser.write_pdu_header(body.len())?;
+    ser.writer.extend_from_slice(&body);
+    Ok(ser.writer)
+}
+
+/// Serialize the given data structure as a Watchman v2 PDU carrying the given
+/// capabilities into the IO stream.
+///
+/// As with `to_writer_pdu`, the writer is flushed once the whole PDU has been
+/// written.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_writer_pdu_v2<W, T>(writer: W, value: &T, caps: Capabilities) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let body = to_vec(value)?;
+    let mut ser = Serializer::native(writer);
+    ser.write_pdu_header_v2(caps, body.len())?;
+    ser.writer.write_all(&body)?;
+    ser.writer.flush()?;
+    Ok(())
+}
+
+/// Serialize the given data structure as a Watchman v2 PDU byte vector
+/// carrying the given capabilities.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_vec_pdu_v2<T>(value: &T, caps: Capabilities) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let body = to_vec(value)?;
+    let mut ser = Serializer::native(Vec::with_capacity(body.len() + 15));
+    ser.write_pdu_header_v2(caps, body.len())?;
+    ser.writer.extend_from_slice(&body);
+    Ok(ser.writer)
 }
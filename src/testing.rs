@@ -0,0 +1,32 @@
+//! Helpers for property testing code which handles BSER.
+//!
+//! Enabled by the `testing` feature.
+
+use crate::value::Value;
+
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+
+/// A strategy generating arbitrary `Value`s, nested up to four deep.
+///
+/// Every tag a `Value` can hold may be produced. Integers span the whole
+/// `i64` range, and as a `Value` doesn't record which width an integer was
+/// read with, they compare equal however compactly they were written.
+/// Reals are never NaN, so that generated values always equal themselves.
+pub fn arb_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::Integer),
+        any::<f64>()
+            .prop_filter("NaN is not equal to itself", |f| !f.is_nan())
+            .prop_map(Value::Real),
+        vec(any::<u8>(), 0..16).prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..8).prop_map(Value::Array),
+            btree_map(vec(any::<u8>(), 0..8), inner, 0..8).prop_map(Value::Object),
+        ]
+    })
+}
@@ -0,0 +1,1011 @@
+//! A dynamically typed representation of BSER documents.
+
+use crate::de::DummyUnitVariantAccess;
+use crate::error::Error;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::convert::TryFrom;
+use core::fmt;
+use core::marker::PhantomData;
+use core::str;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// Any value which can be represented in BSER.
+///
+/// Strings are kept as raw bytes, as BSER strings are not guaranteed to be
+/// valid UTF-8. Object keys are strings, and so are also kept as bytes.
+///
+/// A `Value`, or a reference to one, is itself a `Deserializer`, so a part
+/// of a document can be read into a more specific type once it has been
+/// inspected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(Vec<u8>),
+    Array(Vec<Value>),
+    Object(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    #[inline]
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Real(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Get the raw bytes of a string value.
+    #[inline]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get a string value, if it is valid UTF-8.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|s| str::from_utf8(s).ok())
+    }
+
+    #[inline]
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_object(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Look up a key in an object. Returns `None` if the key is not present,
+    /// or if this value is not an object.
+    #[inline]
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Value> {
+        self.as_object().and_then(|o| o.get(key.as_ref()))
+    }
+
+    /// Look up a value by an RFC 6901 JSON pointer, such as `/files/0/name`.
+    /// Within each segment, `~1` stands for `/` and `~0` for `~`. Returns
+    /// `None` if the pointer is malformed or any segment is not present.
+    ///
+    /// The empty pointer refers to this value itself.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let mut target = self;
+        for token in pointer_tokens(pointer)? {
+            target = match target {
+                Value::Object(o) => o.get(token.as_bytes())?,
+                Value::Array(a) => a.get(parse_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Like `pointer`, but returns a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let mut target = self;
+        for token in pointer_tokens(pointer)? {
+            target = match target {
+                Value::Object(o) => o.get_mut(token.as_bytes())?,
+                Value::Array(a) => a.get_mut(parse_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Convert to a JSON value, failing with `Error::NonUtf8String` if any
+    /// string or key is not valid UTF-8.
+    ///
+    /// Reals which are NaN or infinite fail with `Error::NonFiniteFloat`, as
+    /// JSON can't represent them. This is what `TryFrom` does.
+    #[cfg(feature = "json")]
+    pub fn try_to_json(&self) -> crate::error::Result<serde_json::Value> {
+        self.to_json(false)
+    }
+
+    /// Like `try_to_json`, but replaces invalid UTF-8 sequences in strings
+    /// and keys with U+FFFD.
+    #[cfg(feature = "json")]
+    pub fn to_json_lossy(&self) -> crate::error::Result<serde_json::Value> {
+        self.to_json(true)
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self, lossy: bool) -> crate::error::Result<serde_json::Value> {
+        use crate::error::Error;
+
+        let string = |s: &[u8]| match str::from_utf8(s) {
+            Ok(s) => Ok(s.to_owned()),
+            Err(_) if lossy => Ok(String::from_utf8_lossy(s).into_owned()),
+            Err(_) => Err(Error::NonUtf8String),
+        };
+        Ok(match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Integer(i) => serde_json::Value::from(*i),
+            Value::Real(f) => serde_json::Number::from_f64(*f)
+                .ok_or(Error::NonFiniteFloat)?
+                .into(),
+            Value::String(s) => serde_json::Value::String(string(s)?),
+            Value::Array(a) => a
+                .iter()
+                .map(|v| v.to_json(lossy))
+                .collect::<crate::error::Result<_>>()?,
+            Value::Object(o) => serde_json::Value::Object(
+                o.iter()
+                    .map(|(k, v)| Ok((string(k)?, v.to_json(lossy)?)))
+                    .collect::<crate::error::Result<_>>()?,
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = crate::error::Error;
+
+    fn try_from(value: Value) -> crate::error::Result<Self> {
+        value.try_to_json()
+    }
+}
+
+/// Integers outside the range of an `i64` fail with `Error::IntegerOverflow`,
+/// as they do when serialized.
+#[cfg(feature = "json")]
+impl TryFrom<serde_json::Value> for Value {
+    type Error = crate::error::Error;
+
+    fn try_from(value: serde_json::Value) -> crate::error::Result<Self> {
+        use crate::error::Error;
+
+        Ok(match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None if n.is_u64() => return Err(Error::IntegerOverflow),
+                None => Value::Real(n.as_f64().ok_or(Error::IntegerOverflow)?),
+            },
+            serde_json::Value::String(s) => Value::String(s.into_bytes()),
+            serde_json::Value::Array(a) => Value::Array(
+                a.into_iter()
+                    .map(Value::try_from)
+                    .collect::<crate::error::Result<_>>()?,
+            ),
+            serde_json::Value::Object(o) => Value::Object(
+                o.into_iter()
+                    .map(|(k, v)| Ok((k.into_bytes(), Value::try_from(v)?)))
+                    .collect::<crate::error::Result<_>>()?,
+            ),
+        })
+    }
+}
+
+/// Split a JSON pointer into its unescaped segments, or `None` if it doesn't
+/// start with `/`.
+fn pointer_tokens(pointer: &str) -> Option<impl Iterator<Item = String> + '_> {
+    let mut tokens = pointer.split('/');
+    if !tokens.next()?.is_empty() {
+        return None;
+    }
+    // `~1` must be unescaped first, so that `~01` becomes `~1` and not `/`.
+    Some(tokens.map(|token| token.replace("~1", "/").replace("~0", "~")))
+}
+
+/// Parse an array index from a JSON pointer, which may not have a sign or
+/// leading zeros.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+// ----------------------------------------------------------------------------
+
+/// Helper for serializing byte strings with `serialize_bytes` rather than as
+/// a sequence.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Real(f) => serializer.serialize_f64(*f),
+            Value::String(s) => serializer.serialize_bytes(s),
+            Value::Array(a) => serializer.collect_seq(a),
+            Value::Object(o) => {
+                let mut map = serializer.serialize_map(Some(o.len()))?;
+                for (k, v) in o {
+                    map.serialize_entry(&Bytes(k), v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Helper for deserializing byte strings with `deserialize_bytes` rather than
+/// as a sequence.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    #[inline]
+    fn visit_str<E>(self, v: &str) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v.as_bytes().to_owned()))
+    }
+
+    #[inline]
+    fn visit_string<E>(self, v: String) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v.into_bytes()))
+    }
+
+    #[inline]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v.to_owned()))
+    }
+
+    #[inline]
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(v))
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any BSER value")
+    }
+
+    #[inline]
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    #[inline]
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    #[inline]
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        if v > i64::MAX as u64 {
+            return Err(E::invalid_value(de::Unexpected::Unsigned(v), &self));
+        }
+        Ok(Value::Integer(v as i64))
+    }
+
+    #[inline]
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Real(v))
+    }
+
+    #[inline]
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.as_bytes().to_owned()))
+    }
+
+    #[inline]
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v.into_bytes()))
+    }
+
+    #[inline]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    #[inline]
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    #[inline]
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    #[inline]
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    #[inline]
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        // Don't trust the size hint too far, as it comes from the input.
+        let mut array = Vec::with_capacity(cmp::min(seq.size_hint().unwrap_or(0), 4096));
+        while let Some(v) = seq.next_element()? {
+            array.push(v);
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = BTreeMap::new();
+        while let Some((ByteBuf(k), v)) = map.next_entry()? {
+            object.insert(k, v);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+impl Value {
+    /// Describe the value for an `invalid_type` error.
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            Value::Null => de::Unexpected::Unit,
+            Value::Bool(b) => de::Unexpected::Bool(*b),
+            Value::Integer(i) => de::Unexpected::Signed(*i),
+            Value::Real(f) => de::Unexpected::Float(*f),
+            Value::String(s) => de::Unexpected::Bytes(s),
+            Value::Array(_) => de::Unexpected::Seq,
+            Value::Object(_) => de::Unexpected::Map,
+        }
+    }
+
+    fn invalid_type(&self, exp: &dyn de::Expected) -> Error {
+        de::Error::invalid_type(self.unexpected(), exp)
+    }
+
+    /// Visit a value which is not a string, array or object, for either of
+    /// the `Deserializer` impls.
+    fn visit_scalar<'de, V: Visitor<'de>>(&self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Integer(i) => visitor.visit_i64(i),
+            Value::Real(f) => visitor.visit_f64(f),
+            Value::String(_) | Value::Array(_) | Value::Object(_) => unreachable!(),
+        }
+    }
+}
+
+/// Defines the methods of `Deserializer` shared by `Value` and `&Value`,
+/// which differ only in whether strings and containers are moved out or
+/// borrowed.
+macro_rules! deserialize_value {
+    ($string:expr) => {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::String(s) => $string(s).deserialize_any(visitor),
+                Value::Array(a) => {
+                    let mut seq = SeqDeserializer::new(a.into_iter());
+                    let value = visitor.visit_seq(&mut seq)?;
+                    seq.end()?;
+                    Ok(value)
+                }
+                Value::Object(o) => {
+                    let entries = o.into_iter().map(|(k, v)| ($string(k), v));
+                    let mut map = MapDeserializer::new(entries);
+                    let value = visitor.visit_map(&mut map)?;
+                    map.end()?;
+                    Ok(value)
+                }
+                scalar => scalar.visit_scalar(visitor),
+            }
+        }
+
+        /// Reads either a one-character string, or an integer codepoint as
+        /// written by `Serializer::with_char_as_int`.
+        fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::String(s) => $string(s).deserialize_str(visitor),
+                other => match other.as_i64() {
+                    Some(v) => {
+                        let c = u32::try_from(v).ok().and_then(char::from_u32);
+                        visitor.visit_char(c.ok_or(Error::InvalidChar(v))?)
+                    }
+                    None => Err(other.invalid_type(&visitor)),
+                },
+            }
+        }
+
+        fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::String(s) => $string(s).deserialize_str(visitor),
+                other => Err(other.invalid_type(&visitor)),
+            }
+        }
+
+        fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_str(visitor)
+        }
+
+        fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_str(visitor)
+        }
+
+        fn deserialize_tuple<V: Visitor<'de>>(
+            self,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            match self {
+                Value::Array(a) if a.len() != len => Err(Error::TupleLengthMismatch {
+                    expected: len,
+                    actual: a.len(),
+                }),
+                other => other.deserialize_any(visitor),
+            }
+        }
+
+        fn deserialize_tuple_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.deserialize_tuple(len, visitor)
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self {
+                Value::Null => visitor.visit_none(),
+                other => visitor.visit_some(other),
+            }
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            match self {
+                // `{ "$key": $value }`-style variant
+                Value::Object(o) => {
+                    if o.len() != 1 {
+                        return Err(de::Error::invalid_length(
+                            o.len(),
+                            &"an object with one key",
+                        ));
+                    }
+                    let (variant, value) = o.into_iter().next().unwrap();
+                    visitor.visit_enum(EnumDeserializer {
+                        variant: $string(variant),
+                        value: VariantDeserializer(value),
+                    })
+                }
+                // "$key" style variant
+                Value::String(s) => $string(s).deserialize_enum(name, variants, visitor),
+                other => Err(other.invalid_type(&"enum variant")),
+            }
+        }
+
+        fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.visit_unit()
+        }
+
+        /// Matches the `Deserializer` for encoded BSER.
+        fn is_human_readable(&self) -> bool {
+            false
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+            unit unit_struct seq map struct
+        }
+    };
+}
+
+/// Deserializes a `Value` into any type, as if it had been read from its
+/// encoding.
+impl<'de> Deserializer<'de> for Value {
+    deserialize_value!(|s| StringDeserializer(Cow::Owned(s)));
+}
+
+/// Like the impl for `Value`, but borrows strings where the visitor allows.
+impl<'de> Deserializer<'de> for &'de Value {
+    deserialize_value!(|s: &'de Vec<u8>| StringDeserializer(Cow::Borrowed(&s[..])));
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    #[inline]
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &'de Value {
+    type Deserializer = Self;
+
+    #[inline]
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializes a string from a `Value`, which may be an object key or the
+/// name of a variant.
+struct StringDeserializer<'de>(Cow<'de, [u8]>);
+
+impl<'de> StringDeserializer<'de> {
+    fn visit_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Cow::Borrowed(s) => visitor.visit_borrowed_bytes(s),
+            Cow::Owned(s) => visitor.visit_byte_buf(s),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for StringDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit_bytes(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(str::from_utf8(s)?),
+            Cow::Owned(s) => {
+                let s = String::from_utf8(s).map_err(|err| err.utf8_error())?;
+                visitor.visit_string(s)
+            }
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Map keys cannot be null.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for StringDeserializer<'de> {
+    type Error = Error;
+    type Variant = DummyUnitVariantAccess;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, DummyUnitVariantAccess), Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self)?;
+        Ok((variant, DummyUnitVariantAccess))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for StringDeserializer<'de> {
+    type Deserializer = Self;
+
+    #[inline]
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializes a `{ "$key": $value }`-style variant from a `Value`.
+struct EnumDeserializer<'de, T> {
+    variant: StringDeserializer<'de>,
+    value: VariantDeserializer<T>,
+}
+
+impl<'de, T> de::EnumAccess<'de> for EnumDeserializer<'de, T>
+where
+    T: Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+    type Variant = VariantDeserializer<T>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, VariantDeserializer<T>), Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, self.value))
+    }
+}
+
+/// The value of a `{ "$key": $value }`-style variant.
+struct VariantDeserializer<T>(T);
+
+impl<'de, T> de::VariantAccess<'de> for VariantDeserializer<T>
+where
+    T: Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Deserialize::deserialize(self.0)
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, Error>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.0)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.0.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.0.deserialize_struct("", fields, visitor)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// An `f32` which is encoded as its bit pattern in an integer, rather than as
+/// a `Real`.
+///
+/// BSER only has 64-bit reals, so a plain `f32` is widened to `f64` when it
+/// is serialized. Reading it back as an `f32` gives the same value, but
+/// readers which expect an `f64` see the widened value, and the payload of a
+/// NaN may not survive the conversions. This type keeps every bit.
+#[derive(Clone, Copy, Debug)]
+pub struct F32Bits(pub f32);
+
+impl Serialize for F32Bits {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.0.to_bits() as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for F32Bits {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = i32::deserialize(deserializer)?;
+        Ok(F32Bits(f32::from_bits(bits as u32)))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// The name of the newtype through which `RawValue` is recognised by this
+/// crate's `Serializer` and `Deserializer`.
+pub(crate) const RAW_VALUE_TOKEN: &str = "$serde_bser::private::RawValue";
+
+/// The encoding of a single BSER value, kept as it is rather than being
+/// decoded.
+///
+/// This serializes by writing its bytes out verbatim, and deserializes by
+/// capturing the encoding of whichever value comes next, which allows parts
+/// of a document to be passed along or parsed later. When deserializing from
+/// a slice the bytes are borrowed from it, as long as the field is marked
+/// `#[serde(borrow)]`, and otherwise they are copied. A row of a templated
+/// array has no contiguous encoding, so it is captured as an object holding
+/// the row's present fields.
+///
+/// As a `RawValue` may borrow from the input, it can't be read with
+/// `from_reader`; use `Deserializer::from_reader` and deserialize it from
+/// that instead.
+///
+/// Only this crate's `Serializer` and `Deserializer` support `RawValue`.
+/// The bytes are in the byte order of whichever produced them, and must be
+/// serialized with the same one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawValue<'a> {
+    bytes: Cow<'a, [u8]>,
+}
+
+impl<'a> RawValue<'a> {
+    /// Wrap the encoding of a value, checking that it holds exactly one
+    /// complete value.
+    pub fn from_bytes(bytes: impl Into<Cow<'a, [u8]>>) -> crate::error::Result<Self> {
+        let bytes = bytes.into();
+        let mut de = crate::de::Deserializer::from_slice(&bytes);
+        de.skip_value()?;
+        de.end()?;
+        Ok(RawValue { bytes })
+    }
+
+    /// Encode `value` with the native byte order.
+    pub fn from_value<T: ?Sized + Serialize>(value: &T) -> crate::error::Result<Self> {
+        Ok(RawValue {
+            bytes: Cow::Owned(crate::ser::to_vec(value)?),
+        })
+    }
+
+    /// The encoding of the value.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The encoding of the value, if it is borrowed from the input it was
+    /// deserialized from.
+    #[inline]
+    pub fn borrowed(&self) -> Option<&'a [u8]> {
+        match self.bytes {
+            Cow::Borrowed(bytes) => Some(bytes),
+            Cow::Owned(_) => None,
+        }
+    }
+
+    /// Unwrap the encoding of the value.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes.into_owned()
+    }
+
+    /// Copy the encoding, if it is borrowed, so that it no longer refers to
+    /// the input.
+    #[inline]
+    pub fn into_owned(self) -> RawValue<'static> {
+        RawValue {
+            bytes: Cow::Owned(self.bytes.into_owned()),
+        }
+    }
+}
+
+impl Serialize for RawValue<'_> {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_VALUE_TOKEN, &Bytes(&self.bytes))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(RAW_VALUE_TOKEN, RawValueVisitor(PhantomData))
+    }
+}
+
+struct RawValueVisitor<'a>(PhantomData<RawValue<'a>>);
+
+impl<'de: 'a, 'a> Visitor<'de> for RawValueVisitor<'a> {
+    type Value = RawValue<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a raw BSER value")
+    }
+
+    #[inline]
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<RawValue<'a>, E> {
+        Ok(RawValue {
+            bytes: Cow::Borrowed(v),
+        })
+    }
+
+    #[inline]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<RawValue<'a>, E> {
+        Ok(RawValue {
+            bytes: Cow::Owned(v.to_owned()),
+        })
+    }
+
+    #[inline]
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<RawValue<'a>, E> {
+        Ok(RawValue {
+            bytes: Cow::Owned(v),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// The name of the newtype through which `SharedBytes` is recognised by this
+/// crate's `Deserializer`.
+#[cfg(feature = "bytes")]
+pub(crate) const SHARED_BYTES_TOKEN: &str = "$serde_bser::private::SharedBytes";
+
+#[cfg(feature = "bytes")]
+std::thread_local! {
+    /// The counted reference offered to the `SharedBytes` visitor for the
+    /// string it is being lent, as serde gives no way to pass it directly.
+    static SHARED_BYTES: core::cell::Cell<Option<bytes::Bytes>> = const { core::cell::Cell::new(None) };
+}
+
+/// Offer `shared` to any `SharedBytes` visitor which `f` lends the same
+/// bytes to.
+#[cfg(feature = "bytes")]
+pub(crate) fn lend_shared_bytes<T>(shared: bytes::Bytes, f: impl FnOnce() -> T) -> T {
+    SHARED_BYTES.with(|cell| cell.set(Some(shared)));
+    let result = f();
+    SHARED_BYTES.with(|cell| cell.take());
+    result
+}
+
+/// A string held in a `bytes::Bytes` buffer.
+///
+/// When deserialized with `de::from_bytes`, or by a `Deserializer` over a
+/// `BytesRead`, this takes a counted reference to the input buffer rather
+/// than copying out of it, so large strings can be kept without the value
+/// borrowing from the input. Otherwise the string is copied. It serializes
+/// as a string.
+///
+/// This type is only available with the `bytes` feature.
+#[cfg(feature = "bytes")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct SharedBytes(pub bytes::Bytes);
+
+#[cfg(feature = "bytes")]
+impl Serialize for SharedBytes {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<'de> Deserialize<'de> for SharedBytes {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(SHARED_BYTES_TOKEN, SharedBytesVisitor)
+    }
+}
+
+#[cfg(feature = "bytes")]
+struct SharedBytesVisitor;
+
+#[cfg(feature = "bytes")]
+impl<'de> Visitor<'de> for SharedBytesVisitor {
+    type Value = SharedBytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<SharedBytes, D::Error> {
+        // Other deserializers don't know the token.
+        d.deserialize_byte_buf(self)
+    }
+
+    #[inline]
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<SharedBytes, E> {
+        // Only take the offered reference if it is to these very bytes.
+        let shared = SHARED_BYTES.with(|cell| cell.take());
+        match shared {
+            Some(shared) if shared.as_ptr() == v.as_ptr() && shared.len() == v.len() => {
+                Ok(SharedBytes(shared))
+            }
+            _ => Ok(SharedBytes(bytes::Bytes::copy_from_slice(v))),
+        }
+    }
+
+    #[inline]
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<SharedBytes, E> {
+        Ok(SharedBytes(bytes::Bytes::copy_from_slice(v)))
+    }
+
+    #[inline]
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<SharedBytes, E> {
+        Ok(SharedBytes(v.into()))
+    }
+
+    #[inline]
+    fn visit_str<E>(self, v: &str) -> Result<SharedBytes, E> {
+        Ok(SharedBytes(bytes::Bytes::copy_from_slice(v.as_bytes())))
+    }
+
+    #[inline]
+    fn visit_string<E>(self, v: String) -> Result<SharedBytes, E> {
+        Ok(SharedBytes(v.into_bytes().into()))
+    }
+}
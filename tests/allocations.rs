@@ -0,0 +1,167 @@
+#[cfg(feature = "std")]
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+#[cfg(feature = "std")]
+use serde_bser::de::{from_reader, Deserializer, IoRead};
+use serde_bser::ser::{to_vec, to_vec_exact, Serializer};
+use serde_bser::value::RawValue;
+use serde_derive::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts every allocation made by this test binary.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let value = f();
+    (value, ALLOCATIONS.load(Ordering::SeqCst) - before)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct File<'a> {
+    name: &'a str,
+    #[serde(with = "serde_bytes")]
+    hash: &'a [u8],
+    size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow)]
+    files: RawValue<'a>,
+}
+
+// The counter is shared by every thread, so everything is checked from a
+// single test.
+#[test]
+fn borrowed_reads_do_not_allocate() {
+    let names: Vec<String> = (0..100).map(|i| format!("file{}.rs", i)).collect();
+    let files: Vec<File> = names
+        .iter()
+        .map(|name| File {
+            name,
+            hash: b"\x00\x01\x02",
+            size: 5,
+        })
+        .collect();
+
+    // Only the result vector is allocated.
+    let objects = to_vec(&files).unwrap();
+    let (decoded, count) = allocations(|| from_slice::<Vec<File>>(&objects).unwrap());
+    assert_eq!(decoded, files);
+    assert_eq!(count, 1);
+
+    // As well as the key vector of the template, whose keys are borrowed.
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    files.serialize(&mut ser).unwrap();
+    let templated = ser.into_inner();
+    let (decoded, count) = allocations(|| from_slice::<Vec<File>>(&templated).unwrap());
+    assert_eq!(decoded, files);
+    assert_eq!(count, 2);
+
+    // Raw values are lent out of the input whole.
+    let envelope = to_vec(&Envelope {
+        files: RawValue::from_bytes(&templated[..]).unwrap(),
+    })
+    .unwrap();
+    let (decoded, count) = allocations(|| from_slice::<Envelope>(&envelope).unwrap());
+    assert_eq!(decoded.files.as_bytes(), &templated[..]);
+    assert_eq!(count, 0);
+
+    // A shared string costs an exactly sized `String` and the copy serde
+    // makes of it into the `Arc`, from a slice or from a stream.
+    let name = to_vec(&"x".repeat(1000)).unwrap();
+    let (decoded, count) = allocations(|| from_slice::<Arc<str>>(&name).unwrap());
+    assert_eq!(decoded.len(), 1000);
+    assert_eq!(count, 2);
+    #[cfg(feature = "std")]
+    {
+        let (decoded, count) = allocations(|| from_reader::<_, Arc<str>>(&name[..]).unwrap());
+        assert_eq!(decoded.len(), 1000);
+        assert_eq!(count, 2);
+    }
+
+    // An exactly sized vector is allocated once, where `to_vec` grows its
+    // vector as it goes.
+    let paths: Vec<String> = (0..10_000).map(|i| format!("file{}.rs", i)).collect();
+    let (bytes, count) = allocations(|| to_vec_exact(&paths).unwrap());
+    assert_eq!(bytes, to_vec(&paths).unwrap());
+    assert_eq!(count, 1);
+    let (_, count) = allocations(|| to_vec(&paths).unwrap());
+    assert!(count > 1);
+
+    // Integer keys are formatted on the stack.
+    let keys: BTreeMap<i64, i64> = (0..100).map(|i| (i64::MIN + i, i)).collect();
+    let mut ser = Serializer::native(Vec::with_capacity(4096));
+    let (_, count) = allocations(|| keys.serialize(&mut ser).unwrap());
+    assert_eq!(count, 0);
+    let decoded: BTreeMap<String, i64> = from_slice(&ser.into_inner()).unwrap();
+    assert_eq!(decoded[&i64::MIN.to_string()], 0);
+
+    // A reset deserializer keeps the scratch buffer it grew before, which
+    // strings read from a stream are copied through.
+    #[cfg(feature = "std")]
+    {
+        let first = to_vec(&"x".repeat(1000)).unwrap();
+        let second = to_vec(&"y".repeat(1000)).unwrap();
+        let mut de = Deserializer::from_reader(&first[..]);
+        assert_eq!(StrLen::deserialize(&mut de).unwrap(), StrLen(1000));
+        let (len, count) = allocations(|| {
+            de.reset(IoRead::new(&second[..]));
+            StrLen::deserialize(&mut de).unwrap()
+        });
+        assert_eq!(len, StrLen(1000));
+        assert_eq!(count, 0);
+    }
+}
+
+/// The length of a string, which is only lent to the visitor.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+struct StrLen(usize);
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for StrLen {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrLenVisitor;
+
+        impl serde::de::Visitor<'_> for StrLenVisitor {
+            type Value = StrLen;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<StrLen, E> {
+                Ok(StrLen(v.len()))
+            }
+        }
+
+        deserializer.deserialize_str(StrLenVisitor)
+    }
+}
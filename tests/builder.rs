@@ -0,0 +1,70 @@
+use serde::ser::Serialize;
+use serde_bser::ser::{
+    to_vec, to_vec_be, to_vec_with, to_writer_with, Serializer, SerializerBuilder,
+};
+use serde_derive::Serialize;
+use std::collections::HashMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize)]
+struct File {
+    name: String,
+    size: i32,
+}
+
+fn files() -> Vec<File> {
+    (0..3)
+        .map(|i| File {
+            name: i.to_string(),
+            size: i * 1000,
+        })
+        .collect()
+}
+
+#[test]
+fn defaults_match_serializer() -> Test {
+    assert_eq!(
+        to_vec_with(SerializerBuilder::native(), &files())?,
+        to_vec(&files())?
+    );
+    assert_eq!(
+        to_vec_with(SerializerBuilder::big_endian(), &files())?,
+        to_vec_be(&files())?
+    );
+    assert_eq!(SerializerBuilder::native(), SerializerBuilder::default());
+    Ok(())
+}
+
+#[test]
+fn options_match_serializer() -> Test {
+    let map: HashMap<String, i32> = (0..10).map(|i| (i.to_string(), i)).collect();
+    let value = (files(), map);
+
+    let builder = SerializerBuilder::big_endian()
+        .with_templating(true)
+        .with_sort_keys(true)
+        .with_compact_ints(false)
+        .with_require_utf8_strings(true)
+        .with_reject_non_finite(true);
+
+    let mut ser = Serializer::big_endian(Vec::new())
+        .with_templating(true)
+        .with_sort_keys(true)
+        .with_compact_ints(false)
+        .with_require_utf8_strings(true)
+        .with_reject_non_finite(true);
+    value.serialize(&mut ser)?;
+    let expected = ser.into_inner();
+
+    // The builder is `Copy`, so it can be used again and again.
+    assert_eq!(to_vec_with(builder, &value)?, expected);
+    let mut written = Vec::new();
+    to_writer_with(builder, &mut written, &value)?;
+    assert_eq!(written, expected);
+
+    let mut ser = builder.build(Vec::new());
+    value.serialize(&mut ser)?;
+    assert_eq!(ser.into_inner(), expected);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use serde::de::{Deserializer, Error as _, Visitor};
+#[cfg(feature = "std")]
+use serde_bser::de::from_reader;
+use serde_bser::de::from_slice;
+use serde_bser::ser::to_vec;
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fmt;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+
+/// A hand-written `with` module for a SHA-1 hash, as a user might write it,
+/// which needs the bytes to be lent to it.
+mod sha1 {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(hash: &[u8; 20], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(hash)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 20], D::Error> {
+        struct HashVisitor;
+
+        impl<'de> Visitor<'de> for HashVisitor {
+            type Value = [u8; 20];
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("20 bytes")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<[u8; 20], E> {
+                v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        d.deserialize_bytes(HashVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Hashes {
+    #[serde(with = "serde_bytes")]
+    md5: [u8; 16],
+    #[serde(with = "serde_bytes")]
+    sha256: [u8; 32],
+    #[serde(with = "sha1")]
+    sha1: [u8; 20],
+}
+
+fn hashes() -> Hashes {
+    Hashes {
+        md5: [0xd4; 16],
+        sha256: [0xe3; 32],
+        sha1: [0xda; 20],
+    }
+}
+
+#[test]
+fn fixed_size_byte_arrays() -> Test {
+    let bytes = to_vec(&hashes())?;
+    assert!(bytes
+        .windows(19)
+        .any(|w| w == [TAG_STRING, TAG_INT8, &[16], &[0xd4; 16]].concat()));
+    assert!(bytes
+        .windows(35)
+        .any(|w| w == [TAG_STRING, TAG_INT8, &[32], &[0xe3; 32]].concat()));
+
+    assert_eq!(from_slice::<Hashes>(&bytes)?, hashes());
+    #[cfg(feature = "std")]
+    assert_eq!(from_reader::<_, Hashes>(&bytes[..])?, hashes());
+    Ok(())
+}
+
+#[test]
+fn wrong_lengths_are_rejected() -> Test {
+    #[derive(Serialize)]
+    struct Short<'a> {
+        #[serde(with = "serde_bytes")]
+        md5: &'a [u8],
+        #[serde(with = "serde_bytes")]
+        sha256: &'a [u8],
+        #[serde(with = "serde_bytes")]
+        sha1: &'a [u8],
+    }
+
+    let short = to_vec(&Short {
+        md5: &[0; 16],
+        sha256: &[0; 32],
+        sha1: &[0; 19],
+    })?;
+    let err = from_slice::<Hashes>(&short).unwrap_err();
+    assert_eq!(
+        err.into_inner(),
+        serde_bser::error::Error::invalid_length(19, &"20 bytes")
+    );
+
+    let short = to_vec(&Short {
+        md5: &[0; 15],
+        sha256: &[0; 32],
+        sha1: &[0; 20],
+    })?;
+    assert!(from_slice::<Hashes>(&short).is_err());
+    #[cfg(feature = "std")]
+    assert!(from_reader::<_, Hashes>(&short[..]).is_err());
+    Ok(())
+}
+
+#[test]
+fn plain_arrays_are_arrays() -> Test {
+    // Without a `with` module, serde treats `[u8; N]` as a tuple.
+    let bytes = to_vec(&[1u8; 4])?;
+    assert_eq!(
+        bytes,
+        [
+            TAG_ARRAY,
+            TAG_INT8,
+            &[4],
+            &[0x03, 1, 0x03, 1, 0x03, 1, 0x03, 1]
+        ]
+        .concat()
+    );
+    assert_eq!(from_slice::<[u8; 4]>(&bytes)?, [1; 4]);
+    Ok(())
+}
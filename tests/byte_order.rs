@@ -0,0 +1,105 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_bser::de::{Deserializer, SliceRead};
+use serde_bser::ser::Serializer;
+use serde_derive::{Deserialize, Serialize};
+
+use std::fmt;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Numbers {
+    small: i8,
+    medium: i16,
+    large: i32,
+    huge: i64,
+    real: f64,
+}
+
+fn numbers() -> Numbers {
+    Numbers {
+        small: -5,
+        medium: 0x1234,
+        large: 0x1234_5678,
+        huge: 0x1234_5678_9abc_def0,
+        real: 1.5,
+    }
+}
+
+fn round_trip<B, T>(value: &T) -> Result<(Vec<u8>, T), Box<dyn std::error::Error>>
+where
+    B: ByteOrder,
+    T: Serialize + for<'de> Deserialize<'de> + fmt::Debug,
+{
+    let mut bytes = Vec::new();
+    value.serialize(&mut Serializer::<_, B>::new(&mut bytes))?;
+
+    let mut de = Deserializer::<_, B>::new(SliceRead::new(&bytes));
+    let decoded = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok((bytes, decoded))
+}
+
+#[test]
+fn big_endian_round_trip() -> Test {
+    let (bytes, decoded) = round_trip::<BigEndian, _>(&numbers())?;
+    assert_eq!(decoded, numbers());
+
+    // The `medium` field is written as an Int16 in network byte order.
+    let needle = [0x04, 0x12, 0x34];
+    assert!(bytes.windows(3).any(|w| w == needle));
+    Ok(())
+}
+
+#[test]
+fn little_endian_round_trip() -> Test {
+    let (bytes, decoded) = round_trip::<LittleEndian, _>(&numbers())?;
+    assert_eq!(decoded, numbers());
+
+    let needle = [0x04, 0x34, 0x12];
+    assert!(bytes.windows(3).any(|w| w == needle));
+    Ok(())
+}
+
+#[test]
+fn big_endian_shortcuts() -> Test {
+    let bytes = serde_bser::ser::to_vec_be(&numbers())?;
+    let (expected, _) = round_trip::<BigEndian, _>(&numbers())?;
+    assert_eq!(bytes, expected);
+
+    let decoded: Numbers = serde_bser::de::from_slice_be(&bytes)?;
+    assert_eq!(decoded, numbers());
+
+    #[cfg(feature = "std")]
+    {
+        let decoded: Numbers = serde_bser::de::from_reader_be(&bytes[..])?;
+        assert_eq!(decoded, numbers());
+    }
+
+    let mut de = Deserializer::big_endian(SliceRead::new(&bytes));
+    assert_eq!(Numbers::deserialize(&mut de)?, numbers());
+    de.end()?;
+    Ok(())
+}
+
+#[test]
+fn cross_endian() -> Test {
+    let mut be = Vec::new();
+    numbers().serialize(&mut Serializer::big_endian(&mut be))?;
+    let le = serde_bser::ser::to_vec(&numbers())?;
+
+    // The same value has a different encoding in each byte order, and reading
+    // with the wrong one gives different numbers.
+    if cfg!(target_endian = "little") {
+        assert_ne!(be, le);
+        let crossed: Numbers = serde_bser::de::from_slice(&be)?;
+        assert_ne!(crossed, numbers());
+        assert_eq!(crossed.medium, 0x3412);
+    }
+
+    let decoded: Numbers = serde_bser::de::from_slice_be(&be)?;
+    assert_eq!(decoded, numbers());
+    Ok(())
+}
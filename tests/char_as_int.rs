@@ -0,0 +1,58 @@
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_INT32: &[u8] = &[0x05];
+
+fn to_vec_int<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut ser = Serializer::native(Vec::new()).with_char_as_int(true);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[test]
+fn chars_as_codepoints() -> Test {
+    assert_eq!(to_vec(&'a')?, [TAG_STRING, TAG_INT8, &[1], b"a"].concat());
+    assert_eq!(to_vec_int(&'a')?, [TAG_INT8, &[0x61]].concat());
+
+    for &c in &['a', 'é', '\u{1f980}', '\0'] {
+        assert_eq!(from_slice::<char>(&to_vec_int(&c)?)?, c);
+        assert_eq!(from_slice::<char>(&to_vec(&c)?)?, c);
+    }
+
+    // Keys are still strings.
+    let mut map = BTreeMap::new();
+    map.insert('k', 'v');
+    let bytes = to_vec_int(&map)?;
+    assert_eq!(
+        bytes,
+        to_vec(
+            &[("k", 'v' as i32)]
+                .iter()
+                .cloned()
+                .collect::<BTreeMap<_, _>>()
+        )?
+    );
+    assert_eq!(from_slice::<BTreeMap<char, char>>(&bytes)?, map);
+    Ok(())
+}
+
+#[test]
+fn invalid_codepoints() {
+    for &v in &[0xd800i32, 0x11_0000, -1] {
+        let bytes = [TAG_INT32, &v.to_ne_bytes()].concat();
+        let err = from_slice::<char>(&bytes).unwrap_err();
+        assert_eq!(err.inner(), &Error::InvalidChar(v as i64));
+        assert!(err.is_data());
+    }
+    assert_eq!(
+        Error::InvalidChar(-1).to_string(),
+        "integer `-1` is not a valid char"
+    );
+}
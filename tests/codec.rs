@@ -0,0 +1,77 @@
+#![cfg(feature = "tokio")]
+
+use bytes::BytesMut;
+use futures_util::{SinkExt, StreamExt};
+use serde_bser::codec::BserCodec;
+use serde_bser::error::Error;
+use serde_bser::ser::to_vec_pdu;
+use serde_derive::{Deserialize, Serialize};
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Response {
+    version: String,
+    clock: String,
+}
+
+fn response(i: usize) -> Response {
+    Response {
+        version: "4.9.0".to_owned(),
+        clock: format!("c:1:{}", i),
+    }
+}
+
+#[tokio::test]
+async fn duplex_round_trip() -> Test {
+    // A tiny buffer forces every PDU to arrive over several reads.
+    let (client, server) = tokio::io::duplex(5);
+
+    let writer = tokio::spawn(async move {
+        let mut framed = FramedWrite::new(server, BserCodec::new());
+        for i in 0..10 {
+            framed.send(response(i)).await?;
+        }
+        Ok::<_, Error>(())
+    });
+
+    let mut framed = FramedRead::new(client, BserCodec::<Response>::new());
+    for i in 0..10 {
+        assert_eq!(framed.next().await.unwrap()?, response(i));
+    }
+    assert!(framed.next().await.is_none());
+
+    writer.await??;
+    Ok(())
+}
+
+#[test]
+fn split_header() -> Test {
+    // A length which needs a wider integer, so the header is 7 bytes.
+    let value = "x".repeat(1000);
+    let bytes = to_vec_pdu(&value)?;
+
+    let mut codec = BserCodec::<String>::new();
+    let mut buf = BytesMut::new();
+    for &b in &bytes[..bytes.len() - 1] {
+        buf.extend_from_slice(&[b]);
+        assert!(codec.decode(&mut buf)?.is_none());
+    }
+    buf.extend_from_slice(&bytes[bytes.len() - 1..]);
+    assert_eq!(codec.decode(&mut buf)?, Some(value));
+    assert!(buf.is_empty());
+    Ok(())
+}
+
+#[test]
+fn bad_magic() {
+    let mut codec = BserCodec::<String>::new();
+
+    let mut buf = BytesMut::from(&b"{\"json\": true}"[..]);
+    assert!(matches!(codec.decode(&mut buf), Err(Error::InvalidMagic)));
+
+    // A wrong second byte is caught before the rest of the header arrives.
+    let mut buf = BytesMut::from(&[0x00, 0x07][..]);
+    assert!(matches!(codec.decode(&mut buf), Err(Error::InvalidMagic)));
+}
@@ -0,0 +1,84 @@
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::ser::{to_vec, Serializer};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_INT64: &[u8] = &[0x06];
+
+fn to_vec_wide<T: Serialize>(value: &T) -> Result<Vec<u8>, serde_bser::error::Error> {
+    let mut ser = Serializer::native(Vec::new()).with_compact_ints(false);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[test]
+fn compact_by_default() -> Test {
+    assert_eq!(to_vec(&5i64)?, [TAG_INT8, &[5]].concat());
+    Ok(())
+}
+
+#[test]
+fn wide_ints() -> Test {
+    let bytes = to_vec_wide(&vec![1u8, 2])?;
+
+    // The array length stays compact; only the values are widened.
+    #[rustfmt::skip]
+    let expected = [
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_INT64, &1i64.to_ne_bytes(),
+        TAG_INT64, &2i64.to_ne_bytes(),
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<u8> = from_slice(&bytes)?;
+    assert_eq!(decoded, [1, 2]);
+    Ok(())
+}
+
+#[test]
+fn wide_ints_round_trip_bytes() -> Test {
+    let bytes = [TAG_INT64, &7i64.to_ne_bytes()].concat();
+    let value: i64 = from_slice(&bytes)?;
+    assert_eq!(to_vec_wide(&value)?, bytes);
+    assert_ne!(to_vec(&value)?, bytes);
+    Ok(())
+}
+
+#[test]
+fn every_integer_type_is_widened() -> Test {
+    let expected = [TAG_INT64, &42i64.to_ne_bytes()].concat();
+    assert_eq!(to_vec_wide(&42i32)?, expected);
+    assert_eq!(to_vec(&42i32)?, [TAG_INT8, &[42]].concat());
+
+    assert_eq!(to_vec_wide(&42i8)?, expected);
+    assert_eq!(to_vec_wide(&42i16)?, expected);
+    assert_eq!(to_vec_wide(&42i64)?, expected);
+    assert_eq!(to_vec_wide(&42i128)?, expected);
+    assert_eq!(to_vec_wide(&42u8)?, expected);
+    assert_eq!(to_vec_wide(&42u16)?, expected);
+    assert_eq!(to_vec_wide(&42u32)?, expected);
+    assert_eq!(to_vec_wide(&42u64)?, expected);
+    assert_eq!(to_vec_wide(&42u128)?, expected);
+
+    let mut ser = Serializer::native(Vec::new())
+        .with_compact_ints(false)
+        .with_char_as_int(true);
+    '*'.serialize(&mut ser)?;
+    assert_eq!(ser.into_inner(), expected);
+
+    // Values buffered for a templated array are widened too.
+    let mut row = std::collections::BTreeMap::new();
+    row.insert("v", 42i32);
+    let rows = vec![row.clone(), row];
+    let mut ser = Serializer::native(Vec::new())
+        .with_compact_ints(false)
+        .with_templating(true);
+    rows.serialize(&mut ser)?;
+    let bytes = ser.into_inner();
+    assert!(bytes.ends_with(&[&expected[..], &expected[..]].concat()));
+    Ok(())
+}
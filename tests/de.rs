@@ -0,0 +1,854 @@
+use serde::de::{Deserialize, IgnoredAny};
+#[cfg(feature = "std")]
+use serde_bser::de::from_reader;
+use serde_bser::de::{from_slice, from_slice_partial, from_slice_pdu, Deserializer, ValueKind};
+use serde_bser::error::Error;
+use serde_bser::value::{RawValue, Value};
+use serde_derive::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_INT16: &[u8] = &[0x04];
+const TAG_INT64: &[u8] = &[0x06];
+const TAG_REAL: &[u8] = &[0x07];
+const TAG_TRUE: &[u8] = &[0x08];
+const TAG_FALSE: &[u8] = &[0x09];
+const TAG_TEMPLATED: &[u8] = &[0x0b];
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Borrowed<'a> {
+    name: &'a str,
+    data: &'a [u8],
+}
+
+#[test]
+fn borrowed_str() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[8], b"watchman",
+        TAG_STRING, TAG_INT8, &[4], b"data",
+        TAG_STRING, TAG_INT8, &[2], &[0xff, 0x00],
+    ]
+    .concat();
+
+    let value: Borrowed = from_slice(&bytes)?;
+    assert_eq!(value.name, "watchman");
+    assert_eq!(value.data, &[0xff, 0x00]);
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8_str() {
+    let bytes = [TAG_STRING, TAG_INT8, &[2], &[0xff, 0xfe]].concat();
+
+    match from_slice::<String>(&bytes).map_err(Error::into_inner) {
+        Err(Error::Utf8Error(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let err = from_slice::<String>(&bytes).unwrap_err();
+    let source = std::error::Error::source(&err).unwrap();
+    assert!(source.is::<std::str::Utf8Error>());
+
+    // Raw bytes are still accepted where a byte buffer is expected.
+    let value: &[u8] = from_slice(&bytes).unwrap();
+    assert_eq!(value, &[0xff, 0xfe]);
+}
+
+#[test]
+fn string_type_error_message() {
+    let bytes = [TAG_STRING, TAG_INT8, &[2], b"hi"].concat();
+    let err = from_slice::<i32>(&bytes).unwrap_err();
+    assert_eq!(
+        err.inner().to_string(),
+        "invalid type: string \"hi\", expected number"
+    );
+    assert_eq!(err.offset(), Some(5));
+}
+
+#[test]
+fn unknown_tag_offset() {
+    let bytes = [TAG_STRING, TAG_INT8, &[2], b"hi"].concat();
+    let bytes = [&[0x00, 0x03, 0x03], &bytes[..], &[0x03, 0x01, 0xff]].concat();
+
+    let err = from_slice::<Vec<serde::de::IgnoredAny>>(&bytes).unwrap_err();
+    assert!(matches!(err.inner(), Error::UnknownTag(0xff)));
+    assert_eq!(err.offset(), Some(10));
+    assert_eq!(err.to_string(), "unknown tag 0xff at byte offset 10");
+
+    #[cfg(feature = "std")]
+    {
+        let err = from_reader::<_, Vec<serde::de::IgnoredAny>>(&bytes[..]).unwrap_err();
+        assert_eq!(err.offset(), Some(10));
+    }
+
+    // The first byte past the defined tags is reported as it is.
+    assert_eq!(
+        from_slice::<serde::de::IgnoredAny>(&[0x0d]).map_err(Error::into_inner),
+        Err(Error::UnknownTag(0x0d))
+    );
+}
+
+#[test]
+fn borrowed_str_keys() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b", TAG_INT8, &[2],
+    ]
+    .concat();
+
+    let map: BTreeMap<&str, i32> = from_slice(&bytes)?;
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), [("a", 1), ("b", 2)]);
+    Ok(())
+}
+
+#[test]
+fn non_utf8_keys() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], &[0xff], TAG_INT8, &[1],
+    ]
+    .concat();
+
+    match from_slice::<BTreeMap<String, i32>>(&bytes).map_err(Error::into_inner) {
+        Err(Error::Utf8Error(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let map: BTreeMap<&[u8], i32> = from_slice(&bytes)?;
+    assert_eq!(map[&[0xff][..]], 1);
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Cows<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+    #[serde(borrow)]
+    data: Cow<'a, [u8]>,
+}
+
+#[test]
+fn cow_fields() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[8], b"watchman",
+        TAG_STRING, TAG_INT8, &[4], b"data",
+        TAG_STRING, TAG_INT8, &[2], &[0xff, 0x00],
+    ]
+    .concat();
+
+    let value: Cows = from_slice(&bytes)?;
+    assert!(matches!(value.name, Cow::Borrowed("watchman")));
+    assert!(matches!(value.data, Cow::Borrowed(&[0xff, 0x00])));
+
+    #[cfg(feature = "std")]
+    {
+        // The input can't be borrowed from, even though `Cows` would allow it.
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        let value: Cows = serde::de::Deserialize::deserialize(&mut de)?;
+        assert!(matches!(value.name, Cow::Owned(ref s) if s == "watchman"));
+        assert!(matches!(value.data, Cow::Owned(ref s) if s == &[0xff, 0x00]));
+    }
+    Ok(())
+}
+
+#[test]
+fn peek_kind() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_STRING, TAG_INT8, &[1], b"a",
+        TAG_OBJECT, TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[1], b"b",
+        TAG_INT8, &[5],
+    ]
+    .concat();
+
+    // Accept either a bare name or an object holding one.
+    #[derive(Deserialize)]
+    struct Named {
+        name: String,
+    }
+
+    let mut de = Deserializer::from_slice(&bytes);
+    let mut names = Vec::new();
+    for _ in 0..2 {
+        let name = match de.peek_kind()? {
+            ValueKind::String => serde::de::Deserialize::deserialize(&mut de)?,
+            _ => <Named as serde::de::Deserialize>::deserialize(&mut de)?.name,
+        };
+        names.push(name);
+    }
+    assert_eq!(names, ["a", "b"]);
+
+    // Peeking doesn't consume the tag, however often it's repeated.
+    assert_eq!(de.peek_kind()?, ValueKind::Integer);
+    assert_eq!(de.peek_kind()?, ValueKind::Integer);
+    assert_eq!(<i32 as serde::de::Deserialize>::deserialize(&mut de)?, 5);
+    de.end()?;
+    Ok(())
+}
+
+#[test]
+fn clone_checkpoint() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_STRING, TAG_INT8, &[2], b"hi",
+        TAG_INT8, &[5],
+    ]
+    .concat();
+
+    let mut de = Deserializer::from_slice(&bytes);
+    assert_eq!(de.peek_kind()?, ValueKind::String);
+    let mut checkpoint = de.clone();
+
+    // A failed attempt on one copy leaves the other where it was.
+    assert!(<i32 as serde::de::Deserialize>::deserialize(&mut de).is_err());
+    let s: &str = serde::de::Deserialize::deserialize(&mut checkpoint)?;
+    assert_eq!(s, "hi");
+
+    let mut retry = checkpoint.clone();
+    assert_eq!(
+        <i32 as serde::de::Deserialize>::deserialize(&mut checkpoint)?,
+        5
+    );
+    checkpoint.end()?;
+    assert_eq!(<i64 as serde::de::Deserialize>::deserialize(&mut retry)?, 5);
+    retry.end()?;
+    Ok(())
+}
+
+#[test]
+fn duplicate_keys() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b", TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[3],
+    ]
+    .concat();
+
+    // By default the last value wins.
+    let map: BTreeMap<String, i32> = from_slice(&bytes)?;
+    assert_eq!(map["a"], 3);
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_reject_duplicate_keys(true);
+    let err = <BTreeMap<String, i32> as serde::de::Deserialize>::deserialize(&mut de).unwrap_err();
+    assert_eq!(err, Error::DuplicateKey("a".to_owned()));
+    assert_eq!(err.to_string(), "duplicate key `a`");
+
+    // Keys are only compared within the same object.
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_OBJECT, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[1],
+        TAG_OBJECT, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[2],
+    ]
+    .concat();
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_reject_duplicate_keys(true);
+    let maps = <Vec<BTreeMap<String, i32>> as serde::de::Deserialize>::deserialize(&mut de)?;
+    assert_eq!(maps.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn duplicate_template_keys() {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_TEMPLATED, TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], &[0xff],
+        TAG_STRING, TAG_INT8, &[1], &[0xff],
+        TAG_INT8, &[1],
+        TAG_INT8, &[1], TAG_INT8, &[2],
+    ]
+    .concat();
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_reject_duplicate_keys(true);
+    let err =
+        <Vec<BTreeMap<&[u8], i32>> as serde::de::Deserialize>::deserialize(&mut de).unwrap_err();
+    assert_eq!(err, Error::DuplicateKey("\u{fffd}".to_owned()));
+}
+
+#[test]
+fn integers_from_reals() -> Test {
+    let real = |v: f64| [TAG_REAL, &v.to_ne_bytes()].concat();
+
+    assert_eq!(from_slice::<u32>(&real(3.0))?, 3);
+    assert_eq!(from_slice::<i8>(&real(-128.0))?, -128);
+    assert_eq!(from_slice::<u64>(&real(1e19))?, 10_000_000_000_000_000_000);
+    assert_eq!(from_slice::<f64>(&[TAG_INT8, &[3]].concat())?, 3.0);
+    assert_eq!(from_slice::<u128>(&real(3.0))?, 3);
+    assert_eq!(
+        from_slice::<i128>(&real(-1e18))?,
+        -1_000_000_000_000_000_000
+    );
+    assert_eq!(from_slice::<u128>(&real(1e19))?, 10_000_000_000_000_000_000);
+
+    for &v in &[3.5, f64::NAN, f64::INFINITY, 1e20, -1e19] {
+        let err = from_slice::<u32>(&real(v)).unwrap_err();
+        assert_eq!(err.inner(), &Error::LossyNumberConversion, "{}", v);
+        let err = from_slice::<i128>(&real(v)).unwrap_err();
+        assert_eq!(err.inner(), &Error::LossyNumberConversion, "{}", v);
+    }
+
+    // Whole numbers must fit the target, and negative ones are rejected for
+    // unsigned targets as negative integers are.
+    for &v in &[256.0, 300.0] {
+        let err = from_slice::<u8>(&real(v)).unwrap_err();
+        assert_eq!(err.inner(), &Error::LossyNumberConversion, "{}", v);
+    }
+    let err = from_slice::<i64>(&real(9_223_372_036_854_775_808.0)).unwrap_err();
+    assert_eq!(err.inner(), &Error::LossyNumberConversion);
+    assert_eq!(
+        from_slice::<i64>(&real(-9_223_372_036_854_775_808.0))?,
+        i64::MIN
+    );
+    let err = from_slice::<i8>(&real(128.0)).unwrap_err();
+    assert_eq!(err.inner(), &Error::LossyNumberConversion);
+
+    let err = from_slice::<u32>(&real(-3.0)).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-3));
+    let err = from_slice::<u32>(&[TAG_INT8, &[-3i8 as u8]].concat()).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-3));
+    let err = from_slice::<u128>(&real(-1.0)).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-1));
+    Ok(())
+}
+
+#[test]
+fn lengths_are_never_reals() -> Test {
+    let real = |v: f64| [TAG_REAL, &v.to_ne_bytes()].concat();
+    let string = [TAG_STRING, &real(3.0), b"abc"].concat();
+    let array = [TAG_ARRAY, &real(2.0), TAG_INT8, &[1], TAG_INT8, &[2]].concat();
+
+    let err = from_slice::<String>(&string).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: floating point `3.0`, expected integer at byte offset 10"
+    );
+    assert!(from_slice::<Vec<i32>>(&array).is_err());
+    assert!(from_slice::<IgnoredAny>(&array).is_err());
+    assert!(from_slice::<RawValue>(&array).is_err());
+
+    let pdu = [&[0x00, 0x01][..], &real(2.0), TAG_INT8, &[1]].concat();
+    assert!(from_slice_pdu::<i32>(&pdu).is_err());
+    Ok(())
+}
+
+#[test]
+fn negative_into_unsigned() -> Test {
+    let int64 = |v: i64| [TAG_INT64, &v.to_ne_bytes()].concat();
+
+    assert_eq!(from_slice::<u64>(&int64(i64::MAX))?, i64::MAX as u64);
+    assert_eq!(from_slice::<u64>(&int64(0))?, 0);
+
+    // A counter which passed `i64::MAX` and was written as its bit pattern.
+    let err = from_slice::<u64>(&int64(u64::MAX as i64)).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-1));
+    assert!(err.is_data());
+    assert_eq!(
+        err.to_string(),
+        "integer `-1` is negative, expected an unsigned integer at byte offset 9"
+    );
+
+    let err = from_slice::<u64>(&int64(i64::MIN)).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(i64::MIN));
+    let err = from_slice::<u8>(&[TAG_INT8, &[0xff]].concat()).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-1));
+
+    // Positive values which don't fit are still left to the visitor.
+    assert!(from_slice::<u8>(&int64(256)).is_err());
+    Ok(())
+}
+
+/// Deserializes through `deserialize_byte_buf`, as `serde_bytes` does.
+#[derive(Debug, PartialEq)]
+struct ByteBuf(Vec<u8>);
+
+impl<'de> serde::de::Deserialize<'de> for ByteBuf {
+    fn deserialize<D: serde::de::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v))
+            }
+        }
+
+        d.deserialize_byte_buf(Visitor)
+    }
+}
+
+#[test]
+fn bytes_from_arrays() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_ARRAY, TAG_INT8, &[3],
+        TAG_INT8, &[1],
+        TAG_INT16, &200i16.to_ne_bytes(),
+        TAG_INT8, &[0],
+    ]
+    .concat();
+
+    // Strict by default.
+    assert!(from_slice::<ByteBuf>(&bytes).is_err());
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_bytes_from_arrays(true);
+    assert_eq!(
+        <ByteBuf as serde::de::Deserialize>::deserialize(&mut de)?,
+        ByteBuf(vec![1, 200, 0])
+    );
+    de.end()?;
+
+    // Strings are still accepted.
+    let mut de = Deserializer::from_slice(b"\x02\x03\x02hi");
+    de.set_bytes_from_arrays(true);
+    assert_eq!(
+        <ByteBuf as serde::de::Deserialize>::deserialize(&mut de)?,
+        ByteBuf(b"hi".to_vec())
+    );
+
+    for element in &[&[0x04, 0x00, 0x01][..], &[0x03, 0xff], &[0x02, 0x03, 0x00]] {
+        let bytes = [TAG_ARRAY, TAG_INT8, &[1], element].concat();
+        let mut de = Deserializer::from_slice(&bytes);
+        de.set_bytes_from_arrays(true);
+        assert!(
+            <ByteBuf as serde::de::Deserialize>::deserialize(&mut de).is_err(),
+            "{:?}",
+            element
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn byte_offset_tracks_progress() -> Test {
+    let bytes = serde_bser::ser::to_vec(&("a", 300, [1, 2]))?;
+
+    let mut de = Deserializer::from_slice(&bytes);
+    assert_eq!(de.byte_offset(), 0);
+    assert_eq!(de.peek_kind()?, ValueKind::Array);
+    // Peeking doesn't count as consuming.
+    assert_eq!(de.byte_offset(), 0);
+    let value: (String, i32, Vec<i32>) = serde::de::Deserialize::deserialize(&mut de)?;
+    assert_eq!(value.1, 300);
+    assert_eq!(de.byte_offset(), bytes.len());
+
+    #[cfg(feature = "std")]
+    {
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        assert_eq!(de.peek_kind()?, ValueKind::Array);
+        assert_eq!(de.byte_offset(), 0);
+        de.skip_value()?;
+        assert_eq!(de.byte_offset(), bytes.len());
+    }
+    Ok(())
+}
+
+#[test]
+fn null_as_empty() -> Test {
+    const TAG_NULL: &[u8] = &[0x0a];
+
+    // Strict by default.
+    assert!(from_slice::<Vec<i32>>(TAG_NULL).is_err());
+    assert!(from_slice::<BTreeMap<String, i32>>(TAG_NULL).is_err());
+
+    let mut de = Deserializer::from_slice(TAG_NULL);
+    de.set_null_as_empty(true);
+    let value: Vec<i32> = serde::de::Deserialize::deserialize(&mut de)?;
+    assert!(value.is_empty());
+    de.end()?;
+
+    let mut de = Deserializer::from_slice(TAG_NULL);
+    de.set_null_as_empty(true);
+    let value: BTreeMap<String, i32> = serde::de::Deserialize::deserialize(&mut de)?;
+    assert!(value.is_empty());
+    de.end()?;
+
+    // `Option` still reads `Null` as `None`.
+    let mut de = Deserializer::from_slice(TAG_NULL);
+    de.set_null_as_empty(true);
+    let value: Option<Vec<i32>> = serde::de::Deserialize::deserialize(&mut de)?;
+    assert_eq!(value, None);
+    Ok(())
+}
+
+/// Records whether an integer was visited as signed or unsigned.
+#[derive(Debug, PartialEq)]
+enum Int {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl<'de> serde::de::Deserialize<'de> for Int {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntVisitor;
+
+        impl serde::de::Visitor<'_> for IntVisitor {
+            type Value = Int;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an integer")
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Int, E> {
+                Ok(Int::Signed(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Int, E> {
+                Ok(Int::Unsigned(v))
+            }
+        }
+
+        deserializer.deserialize_any(IntVisitor)
+    }
+}
+
+#[test]
+fn unsigned_integers() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_ARRAY, TAG_INT8, &[3],
+        TAG_INT64, &0xdead_beef_i64.to_ne_bytes(),
+        TAG_INT8, &[0],
+        TAG_INT16, &(-300i16).to_ne_bytes(),
+    ]
+    .concat();
+
+    let value: Vec<Int> = from_slice(&bytes)?;
+    assert_eq!(
+        value,
+        [Int::Signed(0xdead_beef), Int::Signed(0), Int::Signed(-300)]
+    );
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_unsigned_integers(true);
+    let value: Vec<Int> = serde::de::Deserialize::deserialize(&mut de)?;
+    assert_eq!(
+        value,
+        [
+            Int::Unsigned(0xdead_beef),
+            Int::Unsigned(0),
+            Int::Signed(-300)
+        ]
+    );
+
+    // Types which ask for a particular integer are unaffected.
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_unsigned_integers(true);
+    let value: Vec<i64> = serde::de::Deserialize::deserialize(&mut de)?;
+    assert_eq!(value, [0xdead_beef, 0, -300]);
+    Ok(())
+}
+
+#[test]
+fn non_string_keys() {
+    #[rustfmt::skip]
+    let object = [
+        TAG_OBJECT, TAG_INT8, &[1],
+        TAG_INT8, &[5], TAG_INT8, &[1],
+    ]
+    .concat();
+    let err = from_slice::<BTreeMap<String, i32>>(&object).unwrap_err();
+    assert_eq!(err.inner(), &Error::NonStringKeyInObject);
+    assert!(err.is_syntax());
+    assert_eq!(
+        err.to_string(),
+        "object key is not a string at byte offset 4"
+    );
+    assert_eq!(
+        from_slice::<serde_bser::value::Value>(&object).map_err(Error::into_inner),
+        Err(Error::NonStringKeyInObject)
+    );
+
+    #[rustfmt::skip]
+    let templated = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[1], TAG_INT8, &[5],
+        TAG_INT8, &[1], TAG_INT8, &[1],
+    ]
+    .concat();
+    assert_eq!(
+        from_slice::<Vec<BTreeMap<String, i32>>>(&templated).map_err(Error::into_inner),
+        Err(Error::NonStringKeyInObject)
+    );
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Flags {
+    exists: u8,
+    new: i32,
+    is_fresh: bool,
+}
+
+#[test]
+fn bool_as_int() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[6], b"exists", TAG_TRUE,
+        TAG_STRING, TAG_INT8, &[3], b"new", TAG_FALSE,
+        TAG_STRING, TAG_INT8, &[8], b"is_fresh", TAG_TRUE,
+    ]
+    .concat();
+
+    let err = from_slice::<Flags>(&bytes).unwrap_err();
+    assert_eq!(
+        err.into_inner(),
+        Error::Message("invalid type: boolean `true`, expected number".to_owned())
+    );
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_bool_as_int(true);
+    let flags: Flags = serde::de::Deserialize::deserialize(&mut de)?;
+    assert_eq!(
+        flags,
+        Flags {
+            exists: 1,
+            new: 0,
+            is_fresh: true,
+        }
+    );
+
+    // 128-bit integers too.
+    let mut de = Deserializer::from_slice(TAG_TRUE);
+    de.set_bool_as_int(true);
+    assert_eq!(u128::deserialize(&mut de)?, 1);
+    let mut de = Deserializer::from_slice(TAG_FALSE);
+    de.set_bool_as_int(true);
+    assert_eq!(i128::deserialize(&mut de)?, 0);
+
+    // Lengths must still be integers.
+    let bytes = [TAG_STRING, TAG_TRUE, b"a"].concat();
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_bool_as_int(true);
+    let err = <String as serde::de::Deserialize>::deserialize(&mut de).unwrap_err();
+    assert_eq!(
+        err,
+        Error::Message("invalid type: boolean `true`, expected integer".to_owned())
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Shared {
+    name: std::sync::Arc<str>,
+    path: std::rc::Rc<str>,
+}
+
+#[test]
+fn shared_strs() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name", TAG_STRING, TAG_INT8, &[3], b"foo",
+        TAG_STRING, TAG_INT8, &[4], b"path", TAG_STRING, TAG_INT8, &[5], b"a/b.c",
+    ]
+    .concat();
+    let expected = Shared {
+        name: "foo".into(),
+        path: "a/b.c".into(),
+    };
+    assert_eq!(from_slice::<Shared>(&bytes)?, expected);
+    #[cfg(feature = "std")]
+    assert_eq!(from_reader::<_, Shared>(&bytes[..])?, expected);
+
+    // Strings which aren't UTF-8 are rejected rather than converted.
+    let mut bad = bytes;
+    let last = bad.len() - 1;
+    bad[last] = 0xff;
+    assert!(matches!(
+        from_slice::<Shared>(&bad).map_err(Error::into_inner),
+        Err(Error::Utf8Error(_))
+    ));
+    #[cfg(feature = "std")]
+    assert!(matches!(
+        from_reader::<_, Shared>(&bad[..]).map_err(Error::into_inner),
+        Err(Error::Utf8Error(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn read_values_from_a_slice() -> Test {
+    #[rustfmt::skip]
+    let bytes: Vec<u8> = [
+        TAG_ARRAY, TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"next",
+        b"\r\nplain text",
+    ]
+    .concat();
+
+    let (first, rest): (Vec<i32>, _) = from_slice_partial(&bytes)?;
+    assert_eq!(first, [1, 2]);
+    let (second, rest): (&str, _) = from_slice_partial(rest)?;
+    assert_eq!(second, "next");
+    assert_eq!(rest, b"\r\nplain text");
+
+    // Only `from_slice` requires the value to fill the slice.
+    assert_eq!(
+        from_slice::<Vec<i32>>(&bytes).map_err(Error::into_inner),
+        Err(Error::TrailingBytes)
+    );
+    assert_eq!(
+        from_slice_partial::<Vec<i32>>(&bytes[..5]).map_err(Error::into_inner),
+        Err(Error::Eof)
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Point(i32, i32, i32);
+
+#[test]
+fn tuple_lengths_are_checked() -> Test {
+    let array = |len: u8| {
+        let mut bytes = [TAG_ARRAY, TAG_INT8, &[len]].concat();
+        for i in 0..len {
+            bytes.extend([TAG_INT8, &[i]].concat());
+        }
+        bytes
+    };
+
+    assert_eq!(from_slice::<(i32, i32, i32)>(&array(3))?, (0, 1, 2));
+    assert_eq!(from_slice::<Point>(&array(3))?, Point(0, 1, 2));
+    assert_eq!(from_slice::<[i32; 3]>(&array(3))?, [0, 1, 2]);
+
+    for &len in &[0, 2, 4] {
+        let expected = Error::TupleLengthMismatch {
+            expected: 3,
+            actual: len as usize,
+        };
+        let bytes = array(len);
+        let err = from_slice::<(i32, i32, i32)>(&bytes).unwrap_err();
+        assert_eq!(err.inner(), &expected);
+        let err = from_slice::<Point>(&bytes).unwrap_err();
+        assert_eq!(err.inner(), &expected);
+        let err = from_slice::<[i32; 3]>(&bytes).unwrap_err();
+        assert_eq!(err.inner(), &expected);
+        #[cfg(feature = "std")]
+        {
+            let err = from_reader::<_, (i32, i32, i32)>(&bytes[..]).unwrap_err();
+            assert_eq!(err.inner(), &expected);
+        }
+
+        let value: Value = from_slice(&bytes)?;
+        let err = <(i32, i32, i32)>::deserialize(&value).unwrap_err();
+        assert_eq!(err, expected);
+    }
+
+    let err = from_slice::<(i32, i32, i32)>(&array(4)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "tuple length mismatch: expected 3 elements, found 4 at byte offset 3"
+    );
+
+    // The rows of a templated array are counted too.
+    #[rustfmt::skip]
+    let rows = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[1], b"a",
+        TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2],
+    ]
+    .concat();
+    type Row = BTreeMap<String, i32>;
+    let (a, b) = from_slice::<(Row, Row)>(&rows)?;
+    assert_eq!((a["a"], b["a"]), (1, 2));
+    let err = from_slice::<(Row,)>(&rows).unwrap_err();
+    assert_eq!(
+        err.inner(),
+        &Error::TupleLengthMismatch {
+            expected: 1,
+            actual: 2,
+        }
+    );
+
+    #[derive(Deserialize, Debug)]
+    struct Nested {
+        #[allow(dead_code)]
+        rows: (Row,),
+    }
+    #[rustfmt::skip]
+    let nested = [
+        TAG_OBJECT, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[4], b"rows", &rows,
+    ]
+    .concat();
+    let err = from_slice::<Nested>(&nested).unwrap_err();
+    assert!(matches!(err.inner(), Error::TupleLengthMismatch { .. }));
+    Ok(())
+}
+
+#[test]
+fn deserialize_one_field() -> Test {
+    #[rustfmt::skip]
+    let response = [
+        TAG_OBJECT, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[5], b"files",
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_OBJECT, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[4], b"name", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b",
+        TAG_STRING, TAG_INT8, &[5], b"clock",
+        TAG_STRING, TAG_INT8, &[5], b"c:123",
+        TAG_STRING, TAG_INT8, &[5], b"clock",
+        TAG_INT8, &[7],
+    ]
+    .concat();
+    let bytes = [&response[..], &response[..]].concat();
+
+    // The first value of the key is used, and the whole object is consumed
+    // either way.
+    let mut de = Deserializer::from_slice(&bytes);
+    assert_eq!(de.deserialize_field::<&str>("clock")?, Some("c:123"));
+    assert_eq!(de.byte_offset(), response.len());
+    assert_eq!(de.deserialize_field::<i32>("version")?, None);
+    de.end()?;
+
+    #[cfg(feature = "std")]
+    {
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        assert_eq!(
+            de.deserialize_field::<String>("clock")?.as_deref(),
+            Some("c:123")
+        );
+        assert_eq!(de.deserialize_field::<i32>("version")?, None);
+        de.end()?;
+    }
+
+    let err = Deserializer::from_slice(&[0x03, 0x01])
+        .deserialize_field::<i32>("clock")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `1`, expected object"
+    );
+    Ok(())
+}
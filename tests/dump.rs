@@ -0,0 +1,100 @@
+use serde::ser::Serialize;
+use serde_bser::de::dump_to_string;
+use serde_bser::pdu::Capabilities;
+use serde_bser::ser::{to_vec, to_vec_pdu, to_vec_pdu_v2, Serializer};
+use serde_derive::Serialize;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize)]
+struct File {
+    name: &'static str,
+    size: Option<i64>,
+}
+
+#[test]
+fn dump_values() -> Test {
+    let value = (
+        vec![1i64, 300, 1 << 40],
+        1.5,
+        true,
+        (),
+        byte_string(&[b'a', 0xff]),
+    );
+    assert_eq!(
+        dump_to_string(&to_vec(&value)?)?,
+        r#"Array [
+    Array [
+        Int8(1),
+        Int16(300),
+        Int64(1099511627776),
+    ],
+    Real(1.5),
+    True,
+    Null,
+    String(b"a\xff"),
+]"#
+    );
+
+    assert_eq!(dump_to_string(&to_vec(&Vec::<i32>::new())?)?, "Array []");
+    Ok(())
+}
+
+#[test]
+fn dump_templated() -> Test {
+    let files = vec![
+        File {
+            name: "a",
+            size: Some(1),
+        },
+        File {
+            name: "b",
+            size: None,
+        },
+    ];
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    files.serialize(&mut ser)?;
+
+    assert_eq!(
+        dump_to_string(&ser.into_inner())?,
+        r#"Templated["name", "size"] [
+    {
+        "name": String("a"),
+        "size": Int8(1),
+    },
+    {
+        "name": String("b"),
+        "size": Missing,
+    },
+]"#
+    );
+    Ok(())
+}
+
+#[test]
+fn dump_pdus() -> Test {
+    let mut map = std::collections::BTreeMap::new();
+    map.insert("version", "4.9");
+    assert_eq!(
+        dump_to_string(&to_vec_pdu(&map)?)?,
+        "PDU v1, 19 bytes\nObject {\n    \"version\": String(\"4.9\"),\n}"
+    );
+    assert_eq!(
+        dump_to_string(&to_vec_pdu_v2(&5, Capabilities::DISABLE_UNICODE)?)?,
+        "PDU v2, capabilities 0x1, 2 bytes\nInt8(5)"
+    );
+    Ok(())
+}
+
+/// Serializes a byte string with `serialize_bytes`.
+fn byte_string(bytes: &'static [u8]) -> impl Serialize {
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: serde::ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(self.0)
+        }
+    }
+
+    Bytes(bytes)
+}
@@ -0,0 +1,238 @@
+use serde::de::{Deserialize, IgnoredAny};
+use serde::ser::Serialize;
+#[cfg(feature = "std")]
+use serde_bser::de::from_reader;
+use serde_bser::de::{from_slice, from_slice_pdu, Deserializer};
+use serde_bser::error::{Category, Error};
+use serde_bser::ser::{to_vec, Serializer};
+use serde_bser::value::{RawValue, Value};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::io;
+
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+
+#[test]
+fn syntax_errors() {
+    let err = from_slice::<i32>(&[0xff]).unwrap_err();
+    assert_eq!(err.category(), Category::Syntax);
+    assert!(err.is_syntax());
+
+    let err = from_slice::<i32>(&[TAG_INT8, &[1, 2]].concat()).unwrap_err();
+    assert!(matches!(err.inner(), Error::TrailingBytes));
+    assert!(err.is_syntax());
+
+    // Running out of input is a syntax error, not an IO failure.
+    let err = from_slice::<String>(&[TAG_STRING, TAG_INT8, &[5], b"ab"].concat()).unwrap_err();
+    assert!(err.is_syntax());
+    assert!(!err.is_io());
+}
+
+#[test]
+fn eof_errors() {
+    let err = from_slice::<String>(&[TAG_STRING, TAG_INT8, &[5], b"ab"].concat()).unwrap_err();
+    assert_eq!(err.inner(), &Error::Eof);
+    assert!(err.is_eof());
+    assert_eq!(err.to_string(), "unexpected end of input at byte offset 3");
+
+    let err = from_slice::<i32>(&[0xff]).unwrap_err();
+    assert!(!err.is_eof());
+
+    #[cfg(feature = "std")]
+    {
+        let err = Error::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert_eq!(err, Error::Eof);
+        assert!(!err.is_io());
+    }
+}
+
+#[test]
+fn truncated_numbers() {
+    let numbers: &[(u8, usize, &str)] = &[
+        (0x03, 1, "a 1-byte integer"),
+        (0x04, 2, "a 2-byte integer"),
+        (0x05, 4, "a 4-byte integer"),
+        (0x06, 8, "an 8-byte integer"),
+        (0x07, 8, "an 8-byte real"),
+    ];
+    for &(tag, width, what) in numbers {
+        for cut in 0..width {
+            let mut bytes = vec![tag];
+            bytes.resize(1 + cut, 0);
+            // The offset is that of the end of the input.
+            let message = format!(
+                "unexpected end of input while reading {} at byte offset {}",
+                what,
+                bytes.len()
+            );
+
+            let err = from_slice::<f64>(&bytes).unwrap_err();
+            assert_eq!(err.inner(), &Error::Eof);
+            assert!(err.is_eof());
+            assert_eq!(err.to_string(), message);
+
+            // Values which are skipped or captured are read the same way.
+            let err = from_slice::<IgnoredAny>(&bytes).unwrap_err();
+            assert_eq!(err.to_string(), message);
+            let err = from_slice::<RawValue>(&bytes).unwrap_err();
+            assert_eq!(err.to_string(), message);
+
+            #[cfg(feature = "std")]
+            {
+                let err = from_reader::<_, f64>(&bytes[..]).unwrap_err();
+                assert!(err.is_eof());
+                assert_eq!(err.to_string(), message);
+            }
+        }
+    }
+
+    // The same goes for lengths, including that of a PDU.
+    let err = from_slice::<String>(&[TAG_STRING, &[0x05, 0x00]].concat()).unwrap_err();
+    assert!(err.is_eof());
+    assert_eq!(
+        err.to_string(),
+        "unexpected end of input while reading a 4-byte integer at byte offset 3"
+    );
+    let err = from_slice_pdu::<i32>(&[0x00, 0x01, 0x05, 0x00]).unwrap_err();
+    assert!(err.is_eof());
+    assert_eq!(
+        err.to_string(),
+        "unexpected end of input while reading a 4-byte integer at byte offset 4"
+    );
+}
+
+#[test]
+fn data_errors() {
+    let err = from_slice::<String>(&[TAG_INT8, &[1]].concat()).unwrap_err();
+    assert!(matches!(err.inner(), Error::Message(_)));
+    assert_eq!(err.category(), Category::Data);
+    assert!(err.is_data());
+
+    let err = to_vec(&u64::MAX).unwrap_err();
+    assert!(err.is_data());
+
+    let mut map = BTreeMap::new();
+    map.insert(true, 1);
+    let err = to_vec(&map).unwrap_err();
+    assert!(matches!(err, Error::NonStringKey));
+    assert!(err.is_data());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn io_errors() {
+    let err = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+    assert_eq!(err.category(), Category::Io);
+    assert!(err.is_io());
+    assert!(!err.is_syntax());
+    assert!(!err.is_data());
+}
+
+#[test]
+fn equality() {
+    let err = from_slice::<i32>(&[TAG_INT8, &[1, 2]].concat()).unwrap_err();
+    assert_eq!(err.inner(), &Error::TrailingBytes);
+    assert_ne!(err.inner(), &Error::UnknownTag(0xff));
+    assert_eq!(
+        err,
+        from_slice::<i32>(&[TAG_INT8, &[1, 2]].concat()).unwrap_err()
+    );
+
+    let err = from_slice::<String>(&[TAG_INT8, &[1]].concat()).unwrap_err();
+    assert_eq!(
+        err.into_inner(),
+        Error::Message("invalid type: integer `1`, expected string".to_owned())
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn io_equality_is_by_kind() {
+    let a = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+    let b = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "went away"));
+    let c = Error::from(io::Error::from(io::ErrorKind::ConnectionReset));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Listing {
+    files: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    name: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct TypedListing {
+    #[allow(dead_code)]
+    files: Vec<TypedEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TypedEntry {
+    #[allow(dead_code)]
+    name: String,
+}
+
+fn read_detailed<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, Error> {
+    let mut de = Deserializer::from_slice(bytes);
+    de.set_detailed_errors(true);
+    T::deserialize(&mut de)
+}
+
+#[test]
+fn detailed_errors() -> Result<(), Error> {
+    let listing = Listing {
+        files: vec![
+            Entry {
+                name: Value::String(b"a".to_vec()),
+            },
+            Entry {
+                name: Value::Integer(5),
+            },
+        ],
+    };
+    let message = "invalid type: integer `5`, expected string";
+
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    listing.serialize(&mut ser)?;
+    for bytes in &[to_vec(&listing)?, ser.into_inner()] {
+        let err = read_detailed::<TypedListing>(bytes).unwrap_err();
+        assert_eq!(err.path(), Some("/files/1/name"));
+        assert_eq!(err.inner(), &Error::Message(message.to_owned()));
+        assert!(err.is_data());
+        assert_eq!(err.to_string(), format!("at /files/1/name: {}", message));
+
+        // Paths are only recorded when asked for.
+        let err = from_slice::<TypedListing>(bytes).unwrap_err();
+        assert_eq!(err.path(), None);
+        assert_eq!(err.into_inner(), Error::Message(message.to_owned()));
+    }
+
+    #[cfg(feature = "std")]
+    {
+        let bytes = to_vec(&listing)?;
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        de.set_detailed_errors(true);
+        let err = TypedListing::deserialize(&mut de).unwrap_err();
+        assert_eq!(err.path(), Some("/files/1/name"));
+    }
+
+    // Keys are escaped as in a JSON pointer.
+    let mut map = BTreeMap::new();
+    map.insert("src/a~b", vec![Value::Null]);
+    let err = read_detailed::<BTreeMap<String, Vec<i32>>>(&to_vec(&map)?).unwrap_err();
+    assert_eq!(err.path(), Some("/src~1a~0b/0"));
+
+    // Errors other than messages are located by their offset instead.
+    let bytes = to_vec(&listing)?;
+    let err = read_detailed::<TypedListing>(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.inner(), &Error::Eof);
+    assert_eq!(err.path(), None);
+    Ok(())
+}
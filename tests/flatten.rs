@@ -0,0 +1,81 @@
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::ser::{to_vec, Serializer};
+use serde_bser::value::Value;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Response {
+    version: String,
+    clock: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+#[derive(Serialize)]
+struct Wire {
+    version: &'static str,
+    clock: &'static str,
+    warning: &'static str,
+    files: Vec<i32>,
+}
+
+#[test]
+fn flattened_map() -> Test {
+    let bytes = to_vec(&Wire {
+        version: "4.9",
+        clock: "c:1:2",
+        warning: "recrawl",
+        files: vec![1, 2],
+    })?;
+
+    let response: Response = from_slice(&bytes)?;
+    assert_eq!(response.version, "4.9");
+    assert_eq!(response.clock.as_deref(), Some("c:1:2"));
+    assert_eq!(response.extra.len(), 2);
+    assert_eq!(response.extra["warning"].as_str(), Some("recrawl"));
+    assert_eq!(
+        response.extra["files"],
+        Value::Array(vec![Value::Integer(1), Value::Integer(2)])
+    );
+
+    // Flattened structs are serialized as maps of unknown length.
+    let mut ser = Serializer::native(Vec::new()).with_unknown_lengths(true);
+    response.serialize(&mut ser)?;
+    let again: Response = from_slice(&ser.into_inner())?;
+    assert_eq!(again, response);
+    Ok(())
+}
+
+#[test]
+fn flattened_templated_rows() -> Test {
+    let wire = vec![
+        Wire {
+            version: "4.9",
+            clock: "c:1:2",
+            warning: "recrawl",
+            files: vec![],
+        },
+        Wire {
+            version: "5.0",
+            clock: "c:1:3",
+            warning: "none",
+            files: vec![3],
+        },
+    ];
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    wire.serialize(&mut ser)?;
+
+    let responses: Vec<Response> = from_slice(&ser.into_inner())?;
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[1].version, "5.0");
+    assert_eq!(responses[1].extra["warning"].as_str(), Some("none"));
+    assert_eq!(
+        responses[1].extra["files"],
+        Value::Array(vec![Value::Integer(3)])
+    );
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_bser::de::from_slice;
+use serde_bser::ser::{to_vec, Serializer as BserSerializer};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+/// Serializes as a string in human readable formats, and as an integer
+/// otherwise.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Id(u32);
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.0.to_string())
+        } else {
+            serializer.serialize_u32(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map(Id).map_err(serde::de::Error::custom)
+        } else {
+            u32::deserialize(deserializer).map(Id)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Record {
+    id: Id,
+    ids: Vec<Id>,
+    addr: IpAddr,
+}
+
+#[test]
+fn binary_representations() -> Test {
+    let record = Record {
+        id: Id(7),
+        ids: vec![Id(1), Id(2)],
+        addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+    };
+    let bytes = to_vec(&record)?;
+    assert!(!bytes.windows(9).any(|w| w == b"127.0.0.1"));
+    assert_eq!(from_slice::<Record>(&bytes)?, record);
+
+    let value: serde_bser::value::Value = from_slice(&bytes)?;
+    assert_eq!(value.get("id").and_then(|v| v.as_i64()), Some(7));
+    Ok(())
+}
+
+#[test]
+fn map_keys_stay_readable() -> Test {
+    // Keys must be strings, so they use the readable form on both sides.
+    let mut map = BTreeMap::new();
+    map.insert(Id(3), true);
+    let bytes = to_vec(&map)?;
+    assert!(bytes.contains(&b'3'));
+    assert_eq!(from_slice::<BTreeMap<Id, bool>>(&bytes)?, map);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Times {
+    mtime: SystemTime,
+    elapsed: Duration,
+}
+
+#[test]
+fn times_use_compact_structs() -> Test {
+    // A small nanosecond count is written with the narrowest integer tag,
+    // which still reads back into the `u32` field serde expects.
+    let times = Times {
+        mtime: UNIX_EPOCH + Duration::new(1_600_000_000, 5),
+        elapsed: Duration::new(3, 999_999_999),
+    };
+    let bytes = to_vec(&times)?;
+    assert!(bytes.windows(3).any(|w| w == [0x03, 0x05, 0x02]));
+    assert_eq!(from_slice::<Times>(&bytes)?, times);
+
+    let value: serde_bser::value::Value = from_slice(&bytes)?;
+    let elapsed = value.get("elapsed").unwrap();
+    assert_eq!(elapsed.get("secs").and_then(|v| v.as_i64()), Some(3));
+    assert_eq!(
+        elapsed.get("nanos").and_then(|v| v.as_i64()),
+        Some(999_999_999)
+    );
+
+    // A list of them is written as a template, with the same widths.
+    let list = vec![
+        Duration::from_millis(1),
+        Duration::new(u32::MAX as u64 + 1, 0),
+    ];
+    let mut ser = BserSerializer::native(Vec::new()).with_templating(true);
+    list.serialize(&mut ser)?;
+    let bytes = ser.into_inner();
+    assert_eq!(bytes[0], 0x0b);
+    assert_eq!(from_slice::<Vec<Duration>>(&bytes)?, list);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::to_vec;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn in_range_round_trip() -> Test {
+    for &v in &[0i128, -1, i64::MIN as i128, i64::MAX as i128] {
+        let bytes = to_vec(&v)?;
+        assert_eq!(bytes, to_vec(&(v as i64))?);
+        assert_eq!(from_slice::<i128>(&bytes)?, v);
+    }
+    for &v in &[0u128, 300, i64::MAX as u128] {
+        let bytes = to_vec(&v)?;
+        assert_eq!(bytes, to_vec(&(v as i64))?);
+        assert_eq!(from_slice::<u128>(&bytes)?, v);
+    }
+    Ok(())
+}
+
+#[test]
+fn out_of_range_overflows() {
+    for &v in &[i64::MIN as i128 - 1, i64::MAX as i128 + 1, i128::MAX] {
+        assert!(matches!(to_vec(&v), Err(Error::IntegerOverflow)));
+    }
+    for &v in &[i64::MAX as u128 + 1, u128::MAX] {
+        assert!(matches!(to_vec(&v), Err(Error::IntegerOverflow)));
+    }
+}
+
+#[test]
+fn negative_into_u128() -> Test {
+    let bytes = to_vec(&-1i64)?;
+    let err = from_slice::<u128>(&bytes).unwrap_err();
+    assert_eq!(err.inner(), &Error::NegativeUnsigned(-1));
+    Ok(())
+}
+
+#[test]
+fn map_keys() -> Test {
+    let mut map = std::collections::BTreeMap::new();
+    map.insert(u128::from(u64::MAX) * 2, 1);
+    let bytes = to_vec(&map)?;
+    assert!(bytes.windows(20).any(|w| w == b"36893488147419103230"));
+    Ok(())
+}
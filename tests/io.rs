@@ -0,0 +1,386 @@
+#![cfg(feature = "std")]
+
+use serde::Deserialize;
+use serde_bser::de::{
+    from_reader, from_reader_one, from_slice, BufIoRead, Deserializer, IoRead, SliceRead,
+};
+use serde_bser::pdu::Capabilities;
+use serde_bser::ser::{
+    to_vec_pdu, to_vec_pdu_v2, to_writer, to_writer_pdu, to_writer_pdu_v2, PduWriter,
+};
+
+use std::cell::Cell;
+use std::cmp;
+use std::io;
+use std::rc::Rc;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+/// Writer which accepts at most a few bytes per call, like a congested pipe.
+struct TrickleWriter {
+    written: Vec<u8>,
+}
+
+impl io::Write for TrickleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), 3);
+        self.written.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reader which returns at most a few bytes per call.
+struct TrickleReader<'a>(&'a [u8]);
+
+impl io::Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = cmp::min(cmp::min(buf.len(), self.0.len()), 5);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn short_writes() -> Test {
+    let value = "a fairly long string which needs many calls to write".repeat(10);
+
+    let mut writer = TrickleWriter {
+        written: Vec::new(),
+    };
+    to_writer(&mut writer, &value)?;
+
+    let decoded: String = from_slice(&writer.written)?;
+    assert_eq!(decoded, value);
+    Ok(())
+}
+
+#[test]
+fn buffered_reads_across_refills() -> Test {
+    let value: Vec<String> = (0..50).map(|i| "x".repeat(i * 3)).collect();
+    let bytes = serde_bser::ser::to_vec(&value)?;
+
+    // A tiny buffer makes strings span many refills, and the trickle reader
+    // returns short reads from the underlying stream.
+    let mut de = Deserializer::native(BufIoRead::with_capacity(7, TrickleReader(&bytes)));
+    let decoded = Vec::<String>::deserialize(&mut de)?;
+    de.end()?;
+    assert_eq!(decoded, value);
+    Ok(())
+}
+
+/// Reader which fails after yielding some bytes.
+struct FailingReader<'a>(&'a [u8]);
+
+impl io::Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "peer hung up",
+            ));
+        }
+        let n = cmp::min(buf.len(), self.0.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn io_error_source() {
+    let bytes = serde_bser::ser::to_vec(&vec!["a", "b"]).unwrap();
+    let err = from_reader::<_, Vec<String>>(FailingReader(&bytes[..4])).unwrap_err();
+
+    let mut chain = Vec::new();
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&err);
+    while let Some(err) = source {
+        chain.push(err.to_string());
+        source = err.source();
+    }
+    assert_eq!(chain, ["peer hung up at byte offset 4", "peer hung up"]);
+
+    let io_err = std::error::Error::source(&err)
+        .and_then(|e| e.downcast_ref::<io::Error>())
+        .unwrap();
+    assert_eq!(io_err.kind(), io::ErrorKind::ConnectionReset);
+}
+
+#[test]
+fn bufread_strings() -> Test {
+    let value: Vec<String> = (0..50).map(|i| "y".repeat(i * 3)).collect();
+    let mut bytes = serde_bser::ser::to_vec(&value)?;
+    bytes.extend(b"rest");
+
+    // Strings are either within the buffer or span refills, depending on its
+    // size.
+    for &capacity in &[7, 64, 64 * 1024] {
+        let mut reader = io::BufReader::with_capacity(capacity, &bytes[..]);
+
+        let mut de = Deserializer::from_bufread(&mut reader);
+        let decoded = Vec::<String>::deserialize(&mut de)?;
+        drop(de);
+        assert_eq!(decoded, value);
+
+        // The bytes after the value are left in the reader.
+        let mut rest = Vec::new();
+        io::Read::read_to_end(&mut reader, &mut rest)?;
+        assert_eq!(rest, b"rest");
+    }
+    Ok(())
+}
+
+#[test]
+fn slice_read_past_end() -> Test {
+    let mut read = SliceRead::new(b"ab");
+    let mut buf = [0; 4];
+    assert_eq!(io::Read::read(&mut read, &mut buf)?, 2);
+    assert_eq!(&buf[..2], b"ab");
+
+    // Once exhausted, reads report the end of input.
+    assert_eq!(io::Read::read(&mut read, &mut buf)?, 0);
+    assert_eq!(io::Read::read(&mut read, &mut buf)?, 0);
+    Ok(())
+}
+
+/// Writer which counts the bytes written to it so far.
+struct SharedCounter(Rc<Cell<usize>>);
+
+impl io::Write for SharedCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.set(self.0.get() + buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A million small elements, produced one at a time. Each checks that the
+/// elements before it have already reached the writer.
+struct Elements(Rc<Cell<usize>>);
+
+impl serde::Serialize for Elements {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        const LEN: usize = 1_000_000;
+        let mut seq = serializer.serialize_seq(Some(LEN))?;
+        for i in 0..LEN {
+            // The header takes 6 bytes, and each element 2.
+            assert_eq!(self.0.get(), 6 + 2 * i);
+            seq.serialize_element(&((i % 100) as u8))?;
+        }
+        seq.end()
+    }
+}
+
+#[test]
+fn large_sequences_stream() -> Test {
+    let written = Rc::new(Cell::new(0));
+    to_writer(SharedCounter(written.clone()), &Elements(written.clone()))?;
+    assert_eq!(written.get(), 6 + 2 * 1_000_000);
+    Ok(())
+}
+
+#[test]
+fn read_one_value_at_a_time() -> Test {
+    let mut bytes = serde_bser::ser::to_vec(&[1, 2])?;
+    let first_len = bytes.len();
+    bytes.extend(serde_bser::ser::to_vec("next")?);
+    bytes.extend(b"\r\nplain text");
+
+    let mut reader = &bytes[..];
+    let (first, len): (Vec<i32>, _) = from_reader_one(&mut reader)?;
+    assert_eq!(first, [1, 2]);
+    assert_eq!(len, first_len);
+
+    // Values can be read from a reader which trickles data, too.
+    let mut reader = TrickleReader(reader);
+    let (second, len): (String, _) = from_reader_one(&mut reader)?;
+    assert_eq!(second, "next");
+    assert_eq!(len, 7);
+
+    // The reader is left just after the value.
+    let mut rest = Vec::new();
+    io::Read::read_to_end(&mut reader, &mut rest)?;
+    assert_eq!(rest, b"\r\nplain text");
+    Ok(())
+}
+
+#[test]
+fn serde_bytes() -> Test {
+    let data = vec![0u8, 1, 0xff, b'x'];
+    let encoded = serde_bser::ser::to_vec(&serde_bytes::Bytes::new(&data))?;
+
+    // Slices are borrowed from.
+    let bytes: &serde_bytes::Bytes = from_slice(&encoded)?;
+    assert_eq!(&bytes[..], &data[..]);
+    assert_eq!(
+        bytes.as_ptr(),
+        encoded[encoded.len() - data.len()..].as_ptr()
+    );
+
+    // Readers fill an owned buffer, whether or not they buffer themselves.
+    let buf: serde_bytes::ByteBuf = from_reader(&encoded[..])?;
+    assert_eq!(buf.into_vec(), data);
+
+    let mut de = Deserializer::from_bufread(io::BufReader::new(&encoded[..]));
+    let buf = serde_bytes::ByteBuf::deserialize(&mut de)?;
+    de.end()?;
+    assert_eq!(buf.into_vec(), data);
+
+    let mut de = Deserializer::from_bufread(io::BufReader::with_capacity(2, &encoded[..]));
+    let buf = serde_bytes::ByteBuf::deserialize(&mut de)?;
+    de.end()?;
+    assert_eq!(buf.into_vec(), data);
+
+    let mut de = Deserializer::native(BufIoRead::new(&encoded[..]));
+    let buf = serde_bytes::ByteBuf::deserialize(&mut de)?;
+    de.end()?;
+    assert_eq!(buf.into_vec(), data);
+
+    // Slices borrow for owned buffers too.
+    let buf: serde_bytes::ByteBuf = from_slice(&encoded)?;
+    assert_eq!(buf.into_vec(), data);
+    Ok(())
+}
+
+#[test]
+fn owned_strings() -> Test {
+    let names = vec!["a".to_owned(), "b".repeat(5000), String::new()];
+    let encoded = serde_bser::ser::to_vec(&names)?;
+    assert_eq!(from_reader::<_, Vec<String>>(&encoded[..])?, names);
+
+    let mut de = Deserializer::from_bufread(io::BufReader::with_capacity(16, &encoded[..]));
+    assert_eq!(Vec::<String>::deserialize(&mut de)?, names);
+    de.end()?;
+
+    // Strings read into their own buffer are still checked.
+    let invalid = serde_bser::ser::to_vec(&serde_bytes::Bytes::new(&[0xff]))?;
+    assert!(from_reader::<_, String>(&invalid[..]).is_err());
+    Ok(())
+}
+
+/// Reader which is interrupted a few times before each short read, as a
+/// socket read by a process receiving signals may be.
+struct InterruptedReader<'a> {
+    data: &'a [u8],
+    interruptions: usize,
+}
+
+impl io::Read for InterruptedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.interruptions < 3 {
+            self.interruptions += 1;
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        self.interruptions = 0;
+        let n = cmp::min(cmp::min(buf.len(), self.data.len()), 5);
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn interrupted_reads_are_retried() -> Test {
+    let value: Vec<String> = (0..20).map(|i| "x".repeat(i * 3)).collect();
+    let bytes = serde_bser::ser::to_vec(&value)?;
+    let reader = || InterruptedReader {
+        data: &bytes,
+        interruptions: 0,
+    };
+
+    let decoded: Vec<String> = from_reader(reader())?;
+    assert_eq!(decoded, value);
+
+    let mut de = Deserializer::native(BufIoRead::with_capacity(7, reader()));
+    assert_eq!(Vec::<String>::deserialize(&mut de)?, value);
+    de.end()?;
+
+    let mut de = Deserializer::from_bufread(io::BufReader::with_capacity(7, reader()));
+    assert_eq!(Vec::<String>::deserialize(&mut de)?, value);
+    de.end()?;
+    Ok(())
+}
+
+#[test]
+fn reset_reuses_deserializer() -> Test {
+    let first = serde_bser::ser::to_vec_pdu(&vec!["x".repeat(100); 2])?;
+    let second = serde_bser::ser::to_vec_pdu(&[1, 2, 3])?;
+
+    let mut de = Deserializer::from_reader(&first[..]);
+    de.set_max_depth(1);
+    de.read_pdu_header()?;
+    assert_eq!(Vec::<String>::deserialize(&mut de)?.len(), 2);
+    de.end()?;
+
+    de.reset(IoRead::new(&second[..]));
+    de.read_pdu_header()?;
+    assert_eq!(Vec::<i32>::deserialize(&mut de)?, [1, 2, 3]);
+    de.end()?;
+
+    // A peeked value and a half-read PDU are forgotten, but settings such
+    // as limits are kept.
+    de.reset(IoRead::new(&first[..]));
+    de.read_pdu_header()?;
+    de.peek_kind()?;
+    de.reset(IoRead::new(&second[..]));
+    de.read_pdu_header()?;
+    assert_eq!(Vec::<i32>::deserialize(&mut de)?, [1, 2, 3]);
+    de.end()?;
+
+    let nested = serde_bser::ser::to_vec(&[[1]])?;
+    de.reset(IoRead::new(&nested[..]));
+    assert!(Vec::<Vec<i32>>::deserialize(&mut de).is_err());
+    Ok(())
+}
+
+/// Writer which records how many bytes had been written at each flush.
+#[derive(Default)]
+struct FlushRecorder {
+    data: Vec<u8>,
+    flushes: Vec<usize>,
+}
+
+impl io::Write for FlushRecorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.push(self.data.len());
+        Ok(())
+    }
+}
+
+#[test]
+fn pdu_writers_flush() -> Test {
+    let caps = Capabilities::DISABLE_UNICODE;
+    let one = to_vec_pdu(&[1, 2])?.len();
+    let two = to_vec_pdu_v2(&[1, 2], caps)?.len();
+
+    let mut out = FlushRecorder::default();
+    to_writer_pdu(&mut out, &[1, 2])?;
+    to_writer_pdu_v2(&mut out, &[1, 2], caps)?;
+    let mut writer = PduWriter::new();
+    writer.write_pdu(&mut out, &[1, 2])?;
+    writer.write_pdu_v2(&mut out, &[1, 2], caps)?;
+    assert_eq!(
+        out.flushes,
+        [one, one + two, 2 * one + two, 2 * (one + two)]
+    );
+
+    // A buffered writer passes each PDU on as soon as it is written.
+    let mut buffered = io::BufWriter::new(FlushRecorder::default());
+    to_writer_pdu(&mut buffered, &[1, 2])?;
+    assert_eq!(buffered.get_ref().data, to_vec_pdu(&[1, 2])?);
+    Ok(())
+}
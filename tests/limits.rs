@@ -0,0 +1,242 @@
+use serde::Deserialize;
+#[cfg(feature = "std")]
+use serde_bser::de::{from_reader, from_reader_with};
+use serde_bser::de::{from_slice, from_slice_with, Deserializer, ReadLimit};
+use serde_bser::error::Error;
+use serde_bser::value::Value;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+#[cfg(feature = "std")]
+const TAG_INT32: &[u8] = &[0x05];
+const TAG_INT64: &[u8] = &[0x06];
+const TAG_TEMPLATED: &[u8] = &[0x0b];
+
+#[test]
+fn huge_string_length() {
+    let bytes = [TAG_STRING, TAG_INT64, &(1i64 << 40).to_ne_bytes(), b"abc"].concat();
+
+    match from_slice::<String>(&bytes).map_err(Error::into_inner) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    #[cfg(feature = "std")]
+    match from_reader::<_, String>(&bytes[..]).map_err(Error::into_inner) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn large_truncated_string_from_reader() {
+    // Within the default limit, but the data never arrives. This must fail
+    // without allocating the declared length up front.
+    let bytes = [TAG_STRING, TAG_INT32, &(512i32 << 20).to_ne_bytes(), b"abc"].concat();
+
+    match from_reader::<_, String>(&bytes[..]).map_err(Error::into_inner) {
+        Err(Error::Eof) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn configured_max_alloc() {
+    let array = |len: u8| {
+        let mut bytes = [TAG_ARRAY, TAG_INT8, &[len]].concat();
+        for i in 0..len {
+            bytes.extend([TAG_INT8, &[i]].concat());
+        }
+        bytes
+    };
+
+    let bytes = array(4);
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_alloc(4);
+    assert_eq!(Vec::<i32>::deserialize(&mut de).unwrap(), vec![0, 1, 2, 3]);
+
+    let bytes = array(5);
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_alloc(4);
+    match Vec::<i32>::deserialize(&mut de) {
+        Err(Error::LengthLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn count_exceeds_input() {
+    let count = &(i64::MAX).to_ne_bytes();
+    #[rustfmt::skip]
+    let inputs = [
+        [TAG_ARRAY, TAG_INT64, count, TAG_INT8, &[1]].concat(),
+        [TAG_OBJECT, TAG_INT64, count, TAG_INT8, &[1]].concat(),
+        [TAG_TEMPLATED, TAG_ARRAY, TAG_INT64, count].concat(),
+        [
+            TAG_TEMPLATED, TAG_ARRAY, TAG_INT8, &[1], TAG_STRING, TAG_INT8, &[1], b"a",
+            TAG_INT64, count,
+        ]
+        .concat(),
+    ];
+
+    for bytes in &inputs {
+        let mut de = Deserializer::from_slice(bytes);
+        de.set_max_alloc(usize::MAX);
+        match Value::deserialize(&mut de) {
+            Err(Error::LengthExceedsInput) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut de = Deserializer::from_slice(bytes);
+        de.set_max_alloc(usize::MAX);
+        match de.skip_value() {
+            Err(Error::LengthExceedsInput) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    // A count which fits in the remaining input is still accepted.
+    let bytes = [TAG_ARRAY, TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2]].concat();
+    assert_eq!(from_slice::<Vec<i32>>(&bytes).unwrap(), [1, 2]);
+}
+
+fn nested_arrays(depth: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for _ in 0..depth {
+        bytes.extend([TAG_ARRAY, TAG_INT8, &[1]].concat());
+    }
+    bytes.extend([TAG_INT8, &[0]].concat());
+    bytes
+}
+
+#[test]
+fn deeply_nested_arrays() {
+    let bytes = nested_arrays(100_000);
+    match from_slice::<Value>(&bytes).map_err(Error::into_inner) {
+        Err(Error::RecursionLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match from_slice::<serde::de::IgnoredAny>(&bytes).map_err(Error::into_inner) {
+        Err(Error::RecursionLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn configured_max_depth() {
+    let bytes = nested_arrays(3);
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_depth(3);
+    Value::deserialize(&mut de).unwrap();
+
+    let bytes = nested_arrays(4);
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_depth(3);
+    match Value::deserialize(&mut de) {
+        Err(Error::RecursionLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn read_limit_errors() {
+    let bytes = [
+        TAG_ARRAY,
+        TAG_INT8,
+        &[1],
+        TAG_STRING,
+        TAG_INT8,
+        &[3],
+        b"abc",
+    ]
+    .concat();
+    let limit = ReadLimit::new();
+    assert_eq!(
+        from_slice_with::<Value>(limit, &bytes).unwrap(),
+        from_slice::<Value>(&bytes).unwrap()
+    );
+
+    let cases = [
+        (limit.with_max_depth(0), Error::RecursionLimitExceeded),
+        (limit.with_max_alloc(2), Error::LengthLimitExceeded),
+        (
+            limit.with_max_total_bytes(8),
+            Error::TotalBytesLimitExceeded,
+        ),
+    ];
+    for (limit, expected) in &cases {
+        let err = from_slice_with::<Value>(*limit, &bytes).unwrap_err();
+        assert_eq!(err.inner(), expected);
+
+        #[cfg(feature = "std")]
+        {
+            let err = from_reader_with::<_, Value>(*limit, &bytes[..]).unwrap_err();
+            assert_eq!(err.inner(), expected);
+        }
+    }
+
+    // The whole input fits exactly.
+    let limit = limit.with_max_total_bytes(bytes.len());
+    from_slice_with::<Value>(limit, &bytes).unwrap();
+}
+
+#[test]
+fn total_bytes_limit_covers_numbers() {
+    let bytes = [TAG_INT64, &5i64.to_ne_bytes()].concat();
+    let limit = ReadLimit::new().with_max_total_bytes(8);
+    match from_slice_with::<i64>(limit, &bytes).map_err(Error::into_inner) {
+        Err(Error::TotalBytesLimitExceeded) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn configured_max_string_len() {
+    let string = |len: u8| [TAG_STRING, TAG_INT8, &[len], &b"abcde"[..len as usize]].concat();
+    let limit = ReadLimit::new().with_max_string_len(4);
+    assert_eq!(limit.max_string_len(), 4);
+
+    let bytes = string(4);
+    assert_eq!(from_slice_with::<&str>(limit, &bytes).unwrap(), "abcd");
+    assert_eq!(from_slice_with::<String>(limit, &bytes).unwrap(), "abcd");
+    #[cfg(feature = "std")]
+    assert_eq!(
+        from_reader_with::<_, String>(limit, &bytes[..]).unwrap(),
+        "abcd"
+    );
+
+    let bytes = string(5);
+    let expected = Error::StringTooLong { len: 5, limit: 4 };
+    let err = from_slice_with::<&str>(limit, &bytes).unwrap_err();
+    assert_eq!(err.inner(), &expected);
+    assert_eq!(
+        err.to_string(),
+        "string of 5 bytes exceeds limit of 4 at byte offset 3"
+    );
+    let err = from_slice_with::<String>(limit, &bytes).unwrap_err();
+    assert_eq!(err.inner(), &expected);
+    let err = from_slice_with::<Value>(limit, &bytes).unwrap_err();
+    assert_eq!(err.inner(), &expected);
+    #[cfg(feature = "std")]
+    {
+        let err = from_reader_with::<_, String>(limit, &bytes[..]).unwrap_err();
+        assert_eq!(err.inner(), &expected);
+    }
+
+    // Keys are strings too, but the limit doesn't apply to arrays.
+    let bytes = [TAG_OBJECT, TAG_INT8, &[1], &string(5), TAG_INT8, &[1]].concat();
+    let err = from_slice_with::<Value>(limit, &bytes).unwrap_err();
+    assert_eq!(err.inner(), &expected);
+
+    let bytes = [TAG_ARRAY, TAG_INT8, &[5], &[TAG_INT8[0], 0][..].repeat(5)].concat();
+    assert_eq!(
+        from_slice_with::<Vec<i32>>(limit, &bytes).unwrap(),
+        vec![0; 5]
+    );
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_string_len(0);
+    assert_eq!(Vec::<i32>::deserialize(&mut de).unwrap(), vec![0; 5]);
+}
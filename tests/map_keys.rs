@@ -0,0 +1,54 @@
+use serde_bser::de::from_slice;
+use serde_bser::ser::to_vec;
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+
+fn one_key<K: serde::Serialize + Ord>(key: K) -> Result<Vec<u8>, serde_bser::error::Error> {
+    let mut map = BTreeMap::new();
+    map.insert(key, 1);
+    to_vec(&map)
+}
+
+fn expected(key: &str) -> Vec<u8> {
+    [
+        TAG_OBJECT,
+        TAG_INT8,
+        &[1],
+        TAG_STRING,
+        TAG_INT8,
+        &[key.len() as u8],
+        key.as_bytes(),
+        TAG_INT8,
+        &[1],
+    ]
+    .concat()
+}
+
+#[test]
+fn integer_keys_are_decimal_strings() -> Test {
+    // The longest decimal form of each width is written in full.
+    assert_eq!(one_key(i8::MIN)?, expected("-128"));
+    assert_eq!(one_key(u8::MAX)?, expected("255"));
+    assert_eq!(one_key(i32::MIN)?, expected("-2147483648"));
+    assert_eq!(one_key(u32::MAX)?, expected("4294967295"));
+    assert_eq!(one_key(i64::MIN)?, expected("-9223372036854775808"));
+    assert_eq!(one_key(u64::MAX)?, expected("18446744073709551615"));
+    assert_eq!(
+        one_key(i128::MIN)?,
+        expected("-170141183460469231731687303715884105728")
+    );
+    assert_eq!(
+        one_key(u128::MAX)?,
+        expected("340282366920938463463374607431768211455")
+    );
+    assert_eq!(one_key(0u64)?, expected("0"));
+
+    let decoded: BTreeMap<String, i32> = from_slice(&one_key(i64::MIN)?)?;
+    assert_eq!(decoded.keys().next().unwrap(), &i64::MIN.to_string());
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+fn to_vec_finite<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut ser = Serializer::native(Vec::new()).with_reject_non_finite(true);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[test]
+fn non_finite_floats_round_trip_by_default() -> Test {
+    let nan: f64 = from_slice(&to_vec(&f64::NAN)?)?;
+    assert!(nan.is_nan());
+    for &v in &[f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(from_slice::<f64>(&to_vec(&v)?)?, v);
+    }
+    assert_eq!(from_slice::<f32>(&to_vec(&f32::INFINITY)?)?, f32::INFINITY);
+    Ok(())
+}
+
+#[test]
+fn non_finite_floats_are_rejected() -> Test {
+    for &v in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let err = to_vec_finite(&v).unwrap_err();
+        assert_eq!(err, Error::NonFiniteFloat);
+        assert!(err.is_data());
+        assert_eq!(err.to_string(), "float is not finite");
+    }
+    assert_eq!(to_vec_finite(&f32::NAN), Err(Error::NonFiniteFloat));
+    assert_eq!(
+        to_vec_finite(&vec![1.0, f64::INFINITY]),
+        Err(Error::NonFiniteFloat)
+    );
+
+    // Finite values are unchanged.
+    let value = (1.5f64, -0.0f64, f64::MAX, f32::MIN_POSITIVE);
+    assert_eq!(to_vec_finite(&value)?, to_vec(&value)?);
+    Ok(())
+}
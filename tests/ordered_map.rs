@@ -0,0 +1,67 @@
+use indexmap::IndexMap;
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::ser::{to_vec, Serializer};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_TEMPLATED: &[u8] = &[0x0b];
+
+fn unsorted() -> IndexMap<String, i32> {
+    ["size", "name", "exists"]
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.to_string(), i as i32))
+        .collect()
+}
+
+#[test]
+fn objects_keep_wire_order() -> Test {
+    let map = unsorted();
+
+    let bytes = to_vec(&map)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_OBJECT, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[4], b"size", TAG_INT8, &[0],
+        TAG_STRING, TAG_INT8, &[4], b"name", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[6], b"exists", TAG_INT8, &[2],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: IndexMap<String, i32> = from_slice(&bytes)?;
+    assert!(decoded.keys().eq(["size", "name", "exists"].iter()));
+    assert_eq!(decoded, map);
+    Ok(())
+}
+
+#[test]
+fn templates_keep_column_order() -> Test {
+    let rows = vec![unsorted(), unsorted()];
+
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    rows.serialize(&mut ser)?;
+    let bytes = ser.into_inner();
+    #[rustfmt::skip]
+    let header: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[6], b"exists",
+    ]
+    .concat();
+    assert!(bytes.starts_with(&header));
+
+    let decoded: Vec<IndexMap<String, i32>> = from_slice(&bytes)?;
+    for row in &decoded {
+        assert!(row.keys().eq(["size", "name", "exists"].iter()));
+    }
+    assert_eq!(decoded, rows);
+    Ok(())
+}
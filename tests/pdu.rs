@@ -0,0 +1,265 @@
+use serde::de::Deserialize;
+#[cfg(feature = "std")]
+use serde_bser::de::{from_reader_pdu, from_reader_pdu_any, read_pdu_bytes};
+use serde_bser::de::{from_slice_pdu, from_slice_pdu_any, from_slice_pdu_v2, Deserializer};
+use serde_bser::error::Error;
+use serde_bser::pdu::Capabilities;
+use serde_bser::ser::{to_vec_pdu, to_vec_pdu_v2, to_writer_pdu, PduWriter};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const PDU_MAGIC: &[u8] = &[0x00, 0x01];
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_INT8: &[u8] = &[0x03];
+
+fn pdu(parts: &[&[u8]]) -> Vec<u8> {
+    let body: Vec<u8> = parts.concat();
+    let mut out = Vec::new();
+    out.extend(PDU_MAGIC);
+    out.extend(TAG_INT8);
+    out.push(body.len() as u8);
+    out.extend(body);
+    out
+}
+
+#[test]
+fn read_pdu() -> Test {
+    let bytes = pdu(&[TAG_ARRAY, TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2]]);
+
+    let value: Vec<i32> = from_slice_pdu(&bytes)?;
+    assert_eq!(value, vec![1, 2]);
+
+    #[cfg(feature = "std")]
+    {
+        let value: Vec<i32> = from_reader_pdu(&bytes[..])?;
+        assert_eq!(value, vec![1, 2]);
+    }
+    Ok(())
+}
+
+#[test]
+fn read_pdu_bad_magic() {
+    let mut bytes = pdu(&[TAG_INT8, &[5]]);
+    bytes[1] = 0xff;
+
+    match from_slice_pdu::<i32>(&bytes).map_err(Error::into_inner) {
+        Err(Error::InvalidMagic) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn read_pdu_length_mismatch() {
+    // The value ends before the declared body does.
+    let mut bytes = pdu(&[TAG_INT8, &[5]]);
+    bytes[3] += 1;
+    bytes.push(0xff);
+
+    match from_slice_pdu::<i32>(&bytes).map_err(Error::into_inner) {
+        Err(Error::PduLengthMismatch {
+            expected: 3,
+            actual: 2,
+        }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // The value runs past the end of the declared body.
+    let mut bytes = pdu(&[TAG_INT8, &[5]]);
+    bytes[3] -= 1;
+
+    let err = from_slice_pdu::<i32>(&bytes).unwrap_err();
+    assert_eq!(
+        err.inner(),
+        &Error::PduLengthMismatch {
+            expected: 1,
+            actual: 2,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "pdu length mismatch: expected 1 bytes, found 2 at byte offset 6"
+    );
+}
+
+#[test]
+fn write_pdu() -> Test {
+    let expected = pdu(&[TAG_ARRAY, TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2]]);
+    assert_eq!(to_vec_pdu(&[1, 2])?, expected);
+
+    let mut written = Vec::new();
+    to_writer_pdu(&mut written, &[1, 2])?;
+    assert_eq!(written, expected);
+    Ok(())
+}
+
+#[test]
+fn write_pdu_large_body() -> Test {
+    // A body longer than `i8::MAX` needs a wider length integer.
+    let value = vec!["x"; 100];
+    let bytes = to_vec_pdu(&value)?;
+    assert_eq!(&bytes[..3], &[0x00, 0x01, 0x04]);
+
+    let decoded: Vec<String> = from_slice_pdu(&bytes)?;
+    assert_eq!(decoded, value);
+    Ok(())
+}
+
+#[test]
+fn pdu_v2_round_trip() -> Test {
+    // Unknown capability bits must survive the round-trip.
+    let caps = Capabilities::DISABLE_UNICODE | Capabilities::from_bits(0x8000_0000);
+    let bytes = to_vec_pdu_v2(&[1, 2], caps)?;
+
+    let mut expected = vec![0x00, 0x02, 0x01, 0x00, 0x00, 0x80];
+    expected.extend(&pdu(&[TAG_ARRAY, TAG_INT8, &[2], TAG_INT8, &[1], TAG_INT8, &[2]])[2..]);
+    assert_eq!(bytes, expected);
+
+    let (value, read_caps): (Vec<i32>, _) = from_slice_pdu_v2(&bytes)?;
+    assert_eq!(value, vec![1, 2]);
+    assert_eq!(read_caps, caps);
+    assert!(read_caps.contains(Capabilities::DISABLE_UNICODE));
+    assert!(!read_caps.contains(Capabilities::DISABLE_UNICODE_FOR_ERRORS));
+    Ok(())
+}
+
+#[test]
+fn pdu_v2_rejects_v1() -> Test {
+    let bytes = to_vec_pdu(&5)?;
+    match from_slice_pdu_v2::<i32>(&bytes).map_err(Error::into_inner) {
+        Err(Error::InvalidMagic) => Ok(()),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn pdu_writer_reuses_buffer() -> Test {
+    let mut writer = PduWriter::new();
+    let mut out = Vec::new();
+    writer.write_pdu(&mut out, &"x".repeat(300))?;
+    writer.write_pdu(&mut out, &[1, 2])?;
+    writer.write_pdu_v2(&mut out, &5, Capabilities::DISABLE_UNICODE)?;
+
+    let expected = [
+        to_vec_pdu(&"x".repeat(300))?,
+        to_vec_pdu(&[1, 2])?,
+        to_vec_pdu_v2(&5, Capabilities::DISABLE_UNICODE)?,
+    ]
+    .concat();
+    assert_eq!(out, expected);
+    Ok(())
+}
+
+#[test]
+fn end_pdu_allows_following_pdus() -> Test {
+    let bytes = [to_vec_pdu(&[1, 2])?, to_vec_pdu(&"next")?].concat();
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.read_pdu_header()?;
+    let value = Vec::<i32>::deserialize(&mut de)?;
+    assert_eq!(value, [1, 2]);
+    let consumed = de.end_pdu()?;
+    assert_eq!(consumed, to_vec_pdu(&[1, 2])?.len());
+
+    let value: String = from_slice_pdu(&bytes[consumed..])?;
+    assert_eq!(value, "next");
+
+    // `end` stays strict about the bytes which follow.
+    let mut de = Deserializer::from_slice(&bytes);
+    de.read_pdu_header()?;
+    Vec::<i32>::deserialize(&mut de)?;
+    assert_eq!(de.end(), Err(Error::TrailingBytes));
+    Ok(())
+}
+
+#[test]
+fn end_pdu_checks_length() -> Test {
+    let mut bytes = pdu(&[TAG_INT8, &[5]]);
+    bytes[3] += 1;
+    bytes.push(0xff);
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.read_pdu_header()?;
+    i32::deserialize(&mut de)?;
+    assert_eq!(
+        de.end_pdu(),
+        Err(Error::PduLengthMismatch {
+            expected: 3,
+            actual: 2,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn read_any_pdu_header() -> Test {
+    let v1 = to_vec_pdu(&[1, 2])?;
+    let caps = Capabilities::DISABLE_UNICODE;
+    let v2 = to_vec_pdu_v2(&[1, 2], caps)?;
+
+    let mut de = Deserializer::from_slice(&v1);
+    let header = de.read_any_pdu_header()?;
+    assert_eq!(header.version, 1);
+    assert_eq!(header.capabilities, None);
+    assert_eq!(header.body_len, v1.len() - 4);
+
+    let (value, header): (Vec<i32>, _) = from_slice_pdu_any(&v2)?;
+    assert_eq!(value, [1, 2]);
+    assert_eq!(header.version, 2);
+    assert_eq!(header.capabilities, Some(caps));
+    assert_eq!(header.body_len, v2.len() - 8);
+
+    #[cfg(feature = "std")]
+    {
+        let (value, header): (Vec<i32>, _) = from_reader_pdu_any(&v1[..])?;
+        assert_eq!(value, [1, 2]);
+        assert_eq!(header.capabilities, None);
+    }
+
+    // The body length is still checked.
+    let mut bytes = pdu(&[TAG_INT8, &[5]]);
+    bytes[3] += 1;
+    bytes.push(0xff);
+    assert!(matches!(
+        from_slice_pdu_any::<i32>(&bytes).map_err(Error::into_inner),
+        Err(Error::PduLengthMismatch { .. })
+    ));
+
+    let mut bytes = v1;
+    bytes[1] = 0x03;
+    assert_eq!(
+        from_slice_pdu_any::<Vec<i32>>(&bytes).map_err(Error::into_inner),
+        Err(Error::InvalidMagic)
+    );
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn read_pdu_bytes_stops_at_each_pdu() -> Test {
+    let v1 = to_vec_pdu(&"x".repeat(300))?;
+    let v2 = to_vec_pdu_v2(&[1, 2], Capabilities::DISABLE_UNICODE)?;
+    let bytes = [&v1[..], &v2[..]].concat();
+
+    let mut reader = &bytes[..];
+    assert_eq!(read_pdu_bytes(&mut reader)?, Some(v1.clone()));
+    let pdu = read_pdu_bytes(&mut reader)?.unwrap();
+    assert_eq!(pdu, v2);
+    let (value, header): (Vec<i32>, _) = from_slice_pdu_any(&pdu)?;
+    assert_eq!(value, [1, 2]);
+    assert_eq!(header.capabilities, Some(Capabilities::DISABLE_UNICODE));
+    assert_eq!(read_pdu_bytes(&mut reader)?, None);
+
+    // Ending partway through the header or the body is an error.
+    for len in &[1, 3, 6, 7, v2.len() - 1] {
+        assert_eq!(
+            read_pdu_bytes(&v2[..*len]).map_err(Error::into_inner),
+            Err(Error::Eof)
+        );
+    }
+
+    assert_eq!(
+        read_pdu_bytes(&[0x00, 0x03, 0x03, 0x00][..]).map_err(Error::into_inner),
+        Err(Error::InvalidMagic)
+    );
+    Ok(())
+}
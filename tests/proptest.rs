@@ -0,0 +1,23 @@
+#![cfg(feature = "testing")]
+
+use proptest::prelude::*;
+use serde::ser::Serialize;
+use serde_bser::de::{from_slice, from_slice_be};
+use serde_bser::ser::{to_vec, to_vec_be, Serializer};
+use serde_bser::testing::arb_value;
+use serde_bser::value::Value;
+
+proptest! {
+    #[test]
+    fn values_round_trip(value in arb_value()) {
+        prop_assert_eq!(from_slice::<Value>(&to_vec(&value)?)?, value.clone());
+        prop_assert_eq!(from_slice_be::<Value>(&to_vec_be(&value)?)?, value);
+    }
+
+    #[test]
+    fn values_round_trip_through_templates(value in arb_value()) {
+        let mut ser = Serializer::native(Vec::new()).with_templating(true);
+        value.serialize(&mut ser)?;
+        prop_assert_eq!(from_slice::<Value>(&ser.into_inner())?, value);
+    }
+}
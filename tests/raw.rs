@@ -0,0 +1,157 @@
+#[cfg(feature = "std")]
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+#[cfg(feature = "std")]
+use serde_bser::de::Deserializer;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use serde_bser::value::RawValue;
+use serde_derive::{Deserialize, Serialize};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct File {
+    name: String,
+    size: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Envelope<'a> {
+    kind: String,
+    #[serde(borrow)]
+    payload: RawValue<'a>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Decoded {
+    kind: String,
+    payload: Vec<File>,
+}
+
+fn files() -> Vec<File> {
+    vec![
+        File {
+            name: "a".to_owned(),
+            size: 1,
+        },
+        File {
+            name: "b".to_owned(),
+            size: 2,
+        },
+    ]
+}
+
+#[test]
+fn serialize_raw_splices_bytes() -> Test {
+    let mut ser = Serializer::native(Vec::new());
+    ser.serialize_raw(&to_vec(&[1, 2])?)?;
+    assert_eq!(ser.into_inner(), to_vec(&[1, 2])?);
+
+    let envelope = Envelope {
+        kind: "files".to_owned(),
+        payload: RawValue::from_value(&files())?,
+    };
+    let expected = Decoded {
+        kind: "files".to_owned(),
+        payload: files(),
+    };
+    assert_eq!(to_vec(&envelope)?, to_vec(&expected)?);
+    Ok(())
+}
+
+#[test]
+fn capture_raw_value() -> Test {
+    let bytes = to_vec(&Decoded {
+        kind: "files".to_owned(),
+        payload: files(),
+    })?;
+
+    let envelope: Envelope = from_slice(&bytes)?;
+    assert_eq!(envelope.kind, "files");
+    assert_eq!(envelope.payload.as_bytes(), &to_vec(&files())?[..]);
+    assert_eq!(
+        from_slice::<Vec<File>>(envelope.payload.as_bytes())?,
+        files()
+    );
+    assert_eq!(to_vec(&envelope)?, bytes);
+
+    let payload_start = bytes.len() - envelope.payload.as_bytes().len();
+    assert_eq!(envelope.payload.borrowed(), Some(&bytes[payload_start..]));
+
+    // A tag peeked by `Option` is included.
+    let payload = &bytes[payload_start..];
+    let raw: Option<RawValue> = from_slice(payload)?;
+    assert_eq!(raw.and_then(|raw| raw.borrowed()), Some(payload));
+
+    #[cfg(feature = "std")]
+    {
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        let envelope = Envelope::deserialize(&mut de)?;
+        de.end()?;
+        assert_eq!(envelope.payload.as_bytes(), &to_vec(&files())?[..]);
+        assert_eq!(envelope.payload.borrowed(), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn capture_templated_rows() -> Test {
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    files().serialize(&mut ser)?;
+    let bytes = ser.into_inner();
+
+    // The whole templated array is captured as it is.
+    let raw: RawValue = from_slice(&bytes)?;
+    assert_eq!(raw.as_bytes(), &bytes[..]);
+
+    // Each row becomes an object.
+    let rows: Vec<RawValue> = from_slice(&bytes)?;
+    assert_eq!(rows.len(), 2);
+    for (row, file) in rows.iter().zip(files()) {
+        assert_eq!(row.as_bytes(), &to_vec(&file)?[..]);
+    }
+
+    // Raw values are not broken apart to build a template.
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    rows.serialize(&mut ser)?;
+    assert_eq!(ser.into_inner(), to_vec(&files())?);
+    Ok(())
+}
+
+#[test]
+fn from_bytes_checks_value() -> Test {
+    let bytes = to_vec(&files())?;
+    assert_eq!(RawValue::from_bytes(bytes.clone())?.into_bytes(), bytes);
+
+    let incomplete = bytes[..bytes.len() - 1].to_vec();
+    assert!(RawValue::from_bytes(incomplete).is_err());
+
+    let mut trailing = bytes;
+    trailing.push(0x0a);
+    assert_eq!(
+        RawValue::from_bytes(trailing).map_err(Error::into_inner),
+        Err(Error::TrailingBytes)
+    );
+    Ok(())
+}
+
+/// A map whose only key is a raw value.
+struct RawKey(RawValue<'static>);
+
+impl Serialize for RawKey {
+    fn serialize<S: serde::ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(Some((&self.0, 1)))
+    }
+}
+
+#[test]
+fn raw_keys_are_rejected() -> Test {
+    let map = RawKey(RawValue::from_value("a")?);
+    assert_eq!(
+        to_vec(&map).map_err(Error::into_inner),
+        Err(Error::NonStringKey)
+    );
+    Ok(())
+}
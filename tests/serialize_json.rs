@@ -1,6 +1,9 @@
+#![allow(dead_code)]
+
 use serde_bser::ser::to_vec;
 use serde_bser::de::from_slice;
-use serde::{Serialize, Deserialize};
+use serde::ser::Serialize;
+use serde::de::Deserialize;
 use serde_derive::{Serialize, Deserialize};
 use std::collections::BTreeMap;
 
@@ -8,7 +11,7 @@ use std::slice;
 use std::mem;
 use std::fmt;
 
-type Test = Result<(), Box<std::error::Error>>;
+type Test = Result<(), Box<dyn std::error::Error>>;
 
 const TAG_ARRAY: &[u8] = &[0x00];
 const TAG_OBJECT: &[u8] = &[0x01];
@@ -111,3 +114,42 @@ serialize_test!(
         TAG_INT16, bytes(-300_i16),
     ]
 );
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+struct SkippedField {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    age: Option<i32>,
+    year: i32,
+}
+
+// The declared length counts only the fields which are actually written.
+serialize_test!(
+    skipped_field: SkippedField{
+        name: "John Doe".to_owned(),
+        age: None,
+        year: 1976
+    } => [
+        TAG_OBJECT, TAG_INT8, [2],
+        TAG_STRING, TAG_INT8, [4], b"name",
+        TAG_STRING, TAG_INT8, [8], b"John Doe",
+        TAG_STRING, TAG_INT8, [4], b"year",
+        TAG_INT16, bytes(1976_i16),
+    ]
+);
+
+serialize_test!(
+    unskipped_field: SkippedField{
+        name: "John Doe".to_owned(),
+        age: Some(43),
+        year: 1976
+    } => [
+        TAG_OBJECT, TAG_INT8, [3],
+        TAG_STRING, TAG_INT8, [4], b"name",
+        TAG_STRING, TAG_INT8, [8], b"John Doe",
+        TAG_STRING, TAG_INT8, [3], b"age",
+        TAG_INT8, [43],
+        TAG_STRING, TAG_INT8, [4], b"year",
+        TAG_INT16, bytes(1976_i16),
+    ]
+);
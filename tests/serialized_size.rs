@@ -0,0 +1,53 @@
+use serde_bser::ser::{serialized_size, to_vec, to_vec_exact};
+use serde_bser::value::Value;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize)]
+struct File {
+    name: String,
+    size: i64,
+    exists: bool,
+    target: Option<String>,
+}
+
+#[test]
+fn matches_to_vec() -> Test {
+    let file = File {
+        name: "a".repeat(300),
+        size: i64::MAX,
+        exists: true,
+        target: None,
+    };
+    let mut map = BTreeMap::new();
+    map.insert("ratio", Value::Real(0.5));
+    map.insert("list", Value::Array(vec![Value::Integer(1); 200]));
+
+    assert_eq!(serialized_size(&5)?, to_vec(&5)?.len());
+    assert_eq!(serialized_size("")?, to_vec("")?.len());
+    assert_eq!(serialized_size(&file)?, to_vec(&file)?.len());
+    assert_eq!(serialized_size(&map)?, to_vec(&map)?.len());
+    assert_eq!(
+        serialized_size(&vec![(); 1000])?,
+        to_vec(&vec![(); 1000])?.len()
+    );
+    Ok(())
+}
+
+#[test]
+fn errors_are_reported() {
+    let mut map = BTreeMap::new();
+    map.insert(true, 1);
+    assert!(serialized_size(&map).is_err());
+}
+
+#[test]
+fn to_vec_exact_fills_its_capacity() -> Test {
+    let files: Vec<String> = (0..1000).map(|i| format!("file{}.rs", i)).collect();
+    let bytes = to_vec_exact(&files)?;
+    assert_eq!(bytes, to_vec(&files)?);
+    assert_eq!(bytes.capacity(), bytes.len());
+    Ok(())
+}
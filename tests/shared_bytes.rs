@@ -0,0 +1,92 @@
+#![cfg(feature = "bytes")]
+
+use bytes::Bytes;
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+use serde_bser::de::{from_bytes, from_slice, Deserializer};
+use serde_bser::ser::{to_vec, Serializer};
+use serde_bser::value::{SharedBytes, Value};
+use serde_derive::{Deserialize, Serialize};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Blob {
+    name: String,
+    data: SharedBytes,
+    extra: Option<SharedBytes>,
+}
+
+fn blobs() -> Vec<Blob> {
+    (0..3)
+        .map(|i| Blob {
+            name: format!("blob{}", i),
+            data: SharedBytes(Bytes::from(vec![i as u8; 1000])),
+            extra: if i == 1 {
+                Some(SharedBytes(Bytes::from_static(b"extra")))
+            } else {
+                None
+            },
+        })
+        .collect()
+}
+
+/// Whether `inner` lies within `outer`'s memory.
+fn within(inner: &Bytes, outer: &Bytes) -> bool {
+    let range = outer.as_ptr_range();
+    range.contains(&inner.as_ptr()) && inner.as_ptr_range().end <= range.end
+}
+
+#[test]
+fn shared_bytes_reference_the_input() -> Test {
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    blobs().serialize(&mut ser)?;
+    for buffer in [to_vec(&blobs())?, ser.into_inner()] {
+        let buffer = Bytes::from(buffer);
+
+        let decoded: Vec<Blob> = from_bytes(&buffer)?;
+        assert_eq!(decoded, blobs());
+        for blob in &decoded {
+            assert!(within(&blob.data.0, &buffer));
+        }
+        assert!(within(&decoded[1].extra.as_ref().unwrap().0, &buffer));
+
+        // The strings outlive the buffer they were read from.
+        drop(buffer);
+        assert_eq!(decoded, blobs());
+    }
+    Ok(())
+}
+
+#[test]
+fn shared_bytes_are_copied_from_other_inputs() -> Test {
+    let bytes = to_vec(&blobs())?;
+    let decoded: Vec<Blob> = from_slice(&bytes)?;
+    assert_eq!(decoded, blobs());
+    let range = bytes.as_ptr_range();
+    assert!(!range.contains(&decoded[0].data.0.as_ptr()));
+
+    let value: Value = from_slice(&bytes)?;
+    assert_eq!(Vec::<Blob>::deserialize(&value)?, blobs());
+
+    // Either way they encode as plain strings.
+    let plain = Value::String(vec![7; 1000]);
+    assert_eq!(
+        to_vec(&SharedBytes(Bytes::from(vec![7; 1000])))?,
+        to_vec(&plain)?
+    );
+    Ok(())
+}
+
+#[test]
+fn deserializer_from_bytes() -> Test {
+    let buffer = Bytes::from([to_vec(&blobs()[0])?, to_vec(&blobs()[1])?].concat());
+    let mut de = Deserializer::from_bytes(&buffer);
+    let first: Option<SharedBytes> = de.deserialize_field("data")?;
+    let second = Blob::deserialize(&mut de)?;
+    de.end()?;
+    assert!(within(&first.unwrap().0, &buffer));
+    assert!(within(&second.data.0, &buffer));
+    assert_eq!(second, blobs().remove(1));
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use serde::de::Deserialize;
+use serde_bser::de::Deserializer;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use serde_derive::Serialize;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_INT8: &[u8] = &[0x03];
+
+#[derive(Serialize)]
+struct File {
+    name: String,
+    size: Option<i64>,
+    mtime: f64,
+    exists: bool,
+    tags: Vec<String>,
+}
+
+fn files() -> Vec<File> {
+    vec![
+        File {
+            name: "a".to_owned(),
+            size: Some(1 << 40),
+            mtime: 1.5,
+            exists: true,
+            tags: vec!["x".to_owned()],
+        },
+        File {
+            name: "b".to_owned(),
+            size: None,
+            mtime: 2.5,
+            exists: false,
+            tags: vec![],
+        },
+    ]
+}
+
+#[test]
+fn skip_then_read() -> Test {
+    let bytes = [to_vec(&files())?, to_vec(&"after")?].concat();
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.skip_value()?;
+    assert_eq!(String::deserialize(&mut de)?, "after");
+    de.end()?;
+    Ok(())
+}
+
+#[test]
+fn skip_templated() -> Test {
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    serde::Serialize::serialize(&files(), &mut ser)?;
+    let bytes = [ser.into_inner(), to_vec(&5)?].concat();
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.skip_value()?;
+    assert_eq!(i32::deserialize(&mut de)?, 5);
+    de.end()?;
+    Ok(())
+}
+
+#[test]
+fn skip_respects_limits() {
+    let mut bytes = Vec::new();
+    for _ in 0..4 {
+        bytes.extend([TAG_ARRAY, TAG_INT8, &[1]].concat());
+    }
+    bytes.extend([TAG_INT8, &[0]].concat());
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_depth(3);
+    assert_eq!(de.skip_value(), Err(Error::RecursionLimitExceeded));
+
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_max_alloc(0);
+    assert_eq!(de.skip_value(), Err(Error::LengthLimitExceeded));
+}
+
+#[test]
+fn skip_truncated() {
+    let bytes = to_vec(&files()).unwrap();
+    let mut de = Deserializer::from_slice(&bytes[..bytes.len() - 1]);
+    assert!(de.skip_value().unwrap_err().is_syntax());
+}
@@ -0,0 +1,82 @@
+use serde::ser::Serialize;
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+fn to_vec_sorted<T: Serialize>(value: &T, templating: bool) -> Result<Vec<u8>, Error> {
+    let mut ser = Serializer::native(Vec::new())
+        .with_sort_keys(true)
+        .with_templating(templating);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+fn sample() -> HashMap<String, i32> {
+    // Keys of several lengths, including one which needs a wider length
+    // integer, so that sorting by content differs from sorting by encoding.
+    ["b", "aa", "c", "ab", &"z".repeat(200), "a"]
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.to_string(), i as i32))
+        .collect()
+}
+
+#[test]
+fn maps_match_btreemap() -> Test {
+    let map = sample();
+    let sorted: BTreeMap<_, _> = map.clone().into_iter().collect();
+
+    let bytes = to_vec_sorted(&map, false)?;
+    assert_eq!(bytes, to_vec(&sorted)?);
+    assert_eq!(from_slice::<HashMap<String, i32>>(&bytes)?, map);
+
+    // Nested maps are sorted too.
+    let nested = vec![Some(map.clone()), None];
+    assert_eq!(
+        to_vec_sorted(&nested, false)?,
+        to_vec(&vec![Some(sorted), None])?
+    );
+    Ok(())
+}
+
+#[test]
+fn templated_rows_are_sorted() -> Test {
+    let rows = vec![sample(), sample()];
+    let sorted: Vec<BTreeMap<_, _>> = rows
+        .iter()
+        .map(|m| m.clone().into_iter().collect())
+        .collect();
+
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    sorted.serialize(&mut ser)?;
+    assert_eq!(to_vec_sorted(&rows, true)?, ser.into_inner());
+    Ok(())
+}
+
+/// A map which serializes without a length hint.
+struct Unsized;
+
+impl Serialize for Unsized {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map((0..4).filter(|i| i % 2 == 0).map(|i| (i.to_string(), i)))
+    }
+}
+
+#[test]
+fn unknown_lengths_are_still_required() -> Test {
+    assert_eq!(
+        to_vec_sorted(&Unsized, false).map_err(Error::into_inner),
+        Err(Error::LengthRequired)
+    );
+
+    let mut ser = Serializer::native(Vec::new())
+        .with_sort_keys(true)
+        .with_unknown_lengths(true);
+    Unsized.serialize(&mut ser)?;
+    let decoded: BTreeMap<String, i32> = from_slice(&ser.into_inner())?;
+    assert_eq!(decoded.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "std")]
+
+use serde_bser::de::{stream_from_reader, Deserializer, SliceRead};
+use serde_bser::error::Error;
+use serde_bser::ser::to_vec_pdu;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+fn stream() -> Result<Vec<u8>, Error> {
+    Ok([
+        to_vec_pdu(&"first")?,
+        to_vec_pdu(&"second")?,
+        to_vec_pdu(&"third")?,
+    ]
+    .concat())
+}
+
+#[test]
+fn reads_each_pdu() -> Test {
+    let bytes = stream()?;
+
+    let values = stream_from_reader::<_, String>(&bytes[..]).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, ["first", "second", "third"]);
+
+    let values = Deserializer::native(SliceRead::new(&bytes))
+        .into_iter::<&str>()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, ["first", "second", "third"]);
+    Ok(())
+}
+
+#[test]
+fn empty_stream() {
+    assert!(stream_from_reader::<_, String>(&[][..]).next().is_none());
+}
+
+#[test]
+fn byte_offset_between_pdus() -> Test {
+    let first = to_vec_pdu(&"first")?;
+    let bytes = stream()?;
+
+    let mut iter = stream_from_reader::<_, String>(&bytes[..]);
+    assert_eq!(iter.byte_offset(), 0);
+    iter.next().unwrap()?;
+    assert_eq!(iter.byte_offset(), first.len());
+    Ok(())
+}
+
+#[test]
+fn truncated_pdu() -> Test {
+    let bytes = stream()?;
+
+    // Cut the stream partway through the header, then partway through the
+    // body, of the last PDU.
+    let last = bytes.len() - to_vec_pdu(&"third")?.len();
+    for &len in &[last + 1, bytes.len() - 1] {
+        let mut iter = stream_from_reader::<_, String>(&bytes[..len]);
+        assert_eq!(iter.next().unwrap()?, "first");
+        assert_eq!(iter.next().unwrap()?, "second");
+        match iter.next().map(|r| r.map_err(Error::into_inner)) {
+            Some(Err(Error::Eof)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(iter.next().is_none());
+    }
+    Ok(())
+}
+
+#[test]
+fn bad_magic_stops_iteration() -> Test {
+    let mut bytes = stream()?;
+    bytes[1] = 0xff;
+
+    let mut iter = stream_from_reader::<_, String>(&bytes[..]);
+    let err = iter.next().unwrap().unwrap_err();
+    assert!(matches!(err.inner(), Error::InvalidMagic));
+    assert_eq!(err.offset(), Some(2));
+    assert!(iter.next().is_none());
+    Ok(())
+}
@@ -0,0 +1,378 @@
+use serde::ser::Serialize;
+use serde_bser::de::{from_slice, Deserializer};
+use serde_bser::ser::Serializer;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_NULL: &[u8] = &[0x0a];
+const TAG_TEMPLATED: &[u8] = &[0x0b];
+const TAG_MISSING: &[u8] = &[0x0c];
+
+fn to_vec_templated<T: Serialize>(value: &T) -> Result<Vec<u8>, serde_bser::error::Error> {
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct File {
+    name: String,
+    size: i32,
+}
+
+#[test]
+fn homogeneous_structs() -> Test {
+    let files = vec![
+        File {
+            name: "a".to_owned(),
+            size: 1,
+        },
+        File {
+            name: "b".to_owned(),
+            size: 2,
+        },
+    ];
+
+    let bytes = to_vec_templated(&files)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b", TAG_INT8, &[2],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<File> = from_slice(&bytes)?;
+    assert_eq!(decoded, files);
+    Ok(())
+}
+
+#[test]
+fn absent_keys_are_missing() -> Test {
+    let mut first = BTreeMap::new();
+    first.insert("a".to_owned(), 1);
+    first.insert("b".to_owned(), 2);
+    let mut second = BTreeMap::new();
+    second.insert("b".to_owned(), 3);
+    let rows = vec![first, second];
+
+    let bytes = to_vec_templated(&rows)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a",
+        TAG_STRING, TAG_INT8, &[1], b"b",
+        TAG_INT8, &[2],
+        TAG_INT8, &[1], TAG_INT8, &[2],
+        TAG_MISSING, TAG_INT8, &[3],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<BTreeMap<String, i32>> = from_slice(&bytes)?;
+    assert_eq!(decoded, rows);
+    Ok(())
+}
+
+#[test]
+fn heterogeneous_falls_back_to_array() -> Test {
+    let rows = vec![
+        Some(File {
+            name: "a".to_owned(),
+            size: 1,
+        }),
+        None,
+    ];
+
+    let bytes = to_vec_templated(&rows)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[1], b"a",
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_INT8, &[1],
+        TAG_NULL,
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<Option<File>> = from_slice(&bytes)?;
+    assert_eq!(decoded, rows);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Entry {
+    name: String,
+    target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<i32>,
+}
+
+#[test]
+fn none_fields_are_missing() -> Test {
+    let entries = vec![
+        Entry {
+            name: "a".to_owned(),
+            target: None,
+            size: Some(1),
+        },
+        Entry {
+            name: "b".to_owned(),
+            target: Some("c".to_owned()),
+            size: None,
+        },
+    ];
+
+    let bytes = to_vec_templated(&entries)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[3],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[6], b"target",
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_MISSING, TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b", TAG_STRING, TAG_INT8, &[1], b"c", TAG_MISSING,
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<Entry> = from_slice(&bytes)?;
+    assert_eq!(decoded, entries);
+    Ok(())
+}
+
+#[test]
+fn none_fields_outside_templates_are_null() -> Test {
+    let entries = vec![
+        Some(Entry {
+            name: "a".to_owned(),
+            target: None,
+            size: None,
+        }),
+        None,
+    ];
+
+    let bytes = to_vec_templated(&entries)?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[1], b"a",
+        TAG_STRING, TAG_INT8, &[6], b"target",
+        TAG_NULL,
+        TAG_NULL,
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: Vec<Option<Entry>> = from_slice(&bytes)?;
+    assert_eq!(decoded, entries);
+    Ok(())
+}
+
+#[test]
+fn missing_outside_template() {
+    let cases: &[&[u8]] = &[
+        TAG_MISSING,
+        &[0x00, 0x03, 0x01, 0x0c],
+        &[0x01, 0x03, 0x01, 0x02, 0x03, 0x01, b'a', 0x0c],
+    ];
+    for bytes in cases {
+        let err = from_slice::<serde_bser::value::Value>(bytes).unwrap_err();
+        assert_eq!(err.inner(), &serde_bser::error::Error::UnexpectedMissing);
+        assert!(err.is_syntax());
+    }
+
+    let err = from_slice::<Vec<i32>>(&[0x00, 0x03, 0x01, 0x0c]).unwrap_err();
+    assert_eq!(err.inner(), &serde_bser::error::Error::UnexpectedMissing);
+    let err = from_slice::<File>(TAG_MISSING).unwrap_err();
+    assert_eq!(err.inner(), &serde_bser::error::Error::UnexpectedMissing);
+    assert_eq!(
+        err.to_string(),
+        "unexpected missing value, which is only valid within a templated array at byte offset 1"
+    );
+}
+
+#[test]
+fn empty_templates() -> Test {
+    // A template with keys but no rows has no row bodies to read.
+    #[rustfmt::skip]
+    let bytes: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_INT8, &[0],
+    ]
+    .concat();
+    let decoded: Vec<File> = from_slice(&bytes)?;
+    assert!(decoded.is_empty());
+
+    // Whether or not it has keys.
+    let bytes: Vec<u8> = [TAG_TEMPLATED, TAG_ARRAY, TAG_INT8, &[0], TAG_INT8, &[0]].concat();
+    let decoded: Vec<File> = from_slice(&bytes)?;
+    assert!(decoded.is_empty());
+
+    // Without any rows there are no keys, so an empty array is written.
+    let bytes = to_vec_templated(&Vec::<File>::new())?;
+    assert_eq!(bytes, [TAG_ARRAY, TAG_INT8, &[0]].concat());
+    assert!(from_slice::<Vec<File>>(&bytes)?.is_empty());
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Response {
+    Files(Vec<File>),
+    Pair(Vec<File>, i32),
+    Named { files: Vec<File> },
+}
+
+#[test]
+fn enum_variants_with_templated_content() -> Test {
+    let files = || {
+        vec![
+            File {
+                name: "a".to_owned(),
+                size: 1,
+            },
+            File {
+                name: "b".to_owned(),
+                size: 2,
+            },
+        ]
+    };
+
+    let responses = [
+        Response::Files(files()),
+        Response::Pair(files(), 3),
+        Response::Named { files: files() },
+    ];
+    for response in &responses {
+        let bytes = to_vec_templated(response)?;
+        let header = [TAG_TEMPLATED, TAG_ARRAY, TAG_INT8, &[2]].concat();
+        assert!(bytes.windows(header.len()).any(|w| w == &header[..]));
+        let decoded: Response = from_slice(&bytes)?;
+        assert_eq!(&decoded, response);
+    }
+
+    // The variant's content is the templated array itself, within the
+    // object which holds the variant's name.
+    #[rustfmt::skip]
+    let bytes: Vec<u8> = [
+        TAG_OBJECT, TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[5], b"Files",
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[4], b"size",
+        TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"a", TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[1], b"b", TAG_INT8, &[2],
+    ]
+    .concat();
+    assert_eq!(to_vec_templated(&Response::Files(files()))?, bytes);
+    assert_eq!(from_slice::<Response>(&bytes)?, Response::Files(files()));
+
+    // Variants within a sequence are written as objects, rather than as rows
+    // of a template, but keep templating their own content.
+    let rows = vec![Response::Files(files()), Response::Files(files())];
+    let bytes = to_vec_templated(&rows)?;
+    let row = to_vec_templated(&Response::Files(files()))?;
+    assert_eq!(bytes, [TAG_ARRAY, TAG_INT8, &[2], &row, &row].concat());
+    assert_eq!(from_slice::<Vec<Response>>(&bytes)?, rows);
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Columns {
+    col01: i64,
+    #[serde(alias = "forty")]
+    col40: i64,
+    col69: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Strict {
+    col01: i64,
+}
+
+fn read_columns<'de, T: serde::Deserialize<'de>>(
+    bytes: &'de [u8],
+    skip: bool,
+) -> Result<T, serde_bser::error::Error> {
+    let mut de = Deserializer::from_slice(bytes);
+    de.set_skip_unused_columns(skip);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+fn templated_rows(rows: &[BTreeMap<String, Option<i64>>]) -> Vec<u8> {
+    to_vec_templated(&rows).unwrap()
+}
+
+#[test]
+fn skip_unused_columns() -> Test {
+    // More columns than fit in the mask of skipped ones.
+    let row = |columns: i64, missing: i64| -> BTreeMap<String, Option<i64>> {
+        (0..columns)
+            .map(|i| (format!("col{:02}", i), Some(i).filter(|&i| i != missing)))
+            .collect()
+    };
+    let bytes = templated_rows(&[row(70, -1), row(70, 69)]);
+
+    let columns: Vec<Columns> = read_columns(&bytes, true)?;
+    assert_eq!(
+        columns,
+        [
+            Columns {
+                col01: 1,
+                col40: 40,
+                col69: Some(69),
+            },
+            Columns {
+                col01: 1,
+                col40: 40,
+                col69: None,
+            },
+        ]
+    );
+    assert_eq!(read_columns::<Vec<Columns>>(&bytes, false)?, columns);
+
+    // Types other than structs see every column.
+    let maps: Vec<BTreeMap<String, i64>> = read_columns(&bytes, true)?;
+    assert_eq!(maps[0].len(), 70);
+
+    // Unused columns are hidden from a struct which denies unknown fields.
+    let bytes = templated_rows(&[row(3, -1), row(3, -1)]);
+    assert_eq!(read_columns::<Vec<Strict>>(&bytes, true)?[1].col01, 1);
+    assert!(read_columns::<Vec<Strict>>(&bytes, false).is_err());
+
+    // Aliases are among the fields of a struct.
+    let mut aliased = row(2, -1);
+    aliased.insert("forty".to_owned(), Some(50));
+    aliased.insert("col69".to_owned(), None);
+    let bytes = templated_rows(&[aliased.clone(), aliased]);
+    let columns: Vec<Columns> = read_columns(&bytes, true)?;
+    assert_eq!(columns[0].col40, 50);
+    Ok(())
+}
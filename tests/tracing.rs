@@ -0,0 +1,77 @@
+#![cfg(feature = "tracing")]
+
+use serde_bser::de::{from_slice, from_slice_pdu};
+use serde_bser::ser::to_vec_pdu;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Subscriber which records each event as a line of its fields.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+/// Formats the fields of an event.
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        write!(self.0, " {}={:?}", field.name(), value).unwrap();
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line(String::new());
+        event.record(&mut line);
+        self.0.lock().unwrap().push(line.0);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn record(f: impl FnOnce()) -> Vec<String> {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), f);
+    let lines = recorder.0.lock().unwrap().clone();
+    lines
+}
+
+#[test]
+fn events() {
+    let bytes = to_vec_pdu(&vec![vec![1, 2]; 3]).unwrap();
+    let lines = record(|| {
+        from_slice_pdu::<Vec<Vec<i32>>>(&bytes).unwrap();
+    });
+    // Only the outermost container is recorded.
+    assert_eq!(
+        lines,
+        [
+            " message=read bser pdu header len=24",
+            " message=read bser container tag=Array len=3 offset=7",
+        ]
+    );
+
+    let lines = record(|| {
+        from_slice::<i32>(&[0xff]).unwrap_err();
+    });
+    assert_eq!(
+        lines,
+        [" message=bser error offset=0 error=unknown tag 0xff"]
+    );
+}
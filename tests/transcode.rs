@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+use serde::de::Deserialize;
+use serde_bser::de::{Deserializer, StringMode};
+use serde_bser::ser::to_vec;
+use serde_json::json;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+
+fn transcode(bytes: &[u8], string_mode: StringMode) -> Result<String, serde_json::Error> {
+    let mut de = Deserializer::from_slice(bytes);
+    de.set_string_mode(string_mode);
+    let mut out = Vec::new();
+    serde_transcode::transcode(&mut de, &mut serde_json::Serializer::new(&mut out))?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn transcode_with_serde_transcode() -> Test {
+    let value = json!({ "version": "4.9", "files": [{ "name": "a", "size": 3 }] });
+    let bytes = to_vec(&value)?;
+
+    assert_eq!(transcode(&bytes, StringMode::Utf8)?, value.to_string());
+
+    // By default strings are bytes, which JSON only accepts as arrays.
+    assert_eq!(transcode(&to_vec(&"hi")?, StringMode::Bytes)?, "[104,105]");
+    assert!(transcode(&bytes, StringMode::Bytes).is_err());
+    Ok(())
+}
+
+#[test]
+fn transcode_invalid_utf8() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[1],
+        TAG_STRING, TAG_INT8, &[2], &[b'k', 0xff],
+        TAG_STRING, TAG_INT8, &[2], &[0xfe, b'v'],
+    ]
+    .concat();
+
+    assert_eq!(
+        transcode(&bytes, StringMode::Utf8Lossy)?,
+        "{\"k\u{fffd}\":\"\u{fffd}v\"}"
+    );
+    assert!(transcode(&bytes, StringMode::Utf8).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn transcode_to_json_writer() -> Test {
+    use serde_bser::de::transcode_to_json_writer;
+    use serde_bser::error::Error;
+    use serde_bser::ser::to_vec_pdu;
+
+    let mut bytes = to_vec_pdu(&json!({ "name": "abc", "ok": true }))?;
+    let mut out = Vec::new();
+    transcode_to_json_writer(&bytes[..], &mut out, false)?;
+    assert_eq!(out, br#"{"name":"abc","ok":true}"#);
+
+    // Corrupt the "abc" string.
+    let pos = bytes.windows(3).position(|w| w == b"abc").unwrap();
+    bytes[pos] = 0xff;
+
+    let mut out = Vec::new();
+    transcode_to_json_writer(&bytes[..], &mut out, true)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        "{\"name\":\"\u{fffd}bc\",\"ok\":true}"
+    );
+
+    let err = transcode_to_json_writer(&bytes[..], Vec::new(), false).unwrap_err();
+    assert!(matches!(err.inner(), Error::Message(_)));
+    Ok(())
+}
+
+#[test]
+fn utf8_or_bytes() -> Test {
+    #[rustfmt::skip]
+    let bytes = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[4], b"name",
+        TAG_STRING, TAG_INT8, &[2], b"ok",
+        TAG_STRING, TAG_INT8, &[3], b"raw",
+        TAG_STRING, TAG_INT8, &[2], &[0xff, 0x01],
+    ]
+    .concat();
+
+    // Only the string which isn't UTF-8 is left as bytes.
+    assert_eq!(
+        transcode(&bytes, StringMode::Utf8OrBytes)?,
+        r#"{"name":"ok","raw":[255,1]}"#
+    );
+
+    let bytes = to_vec(&json!({ "name": "ok" }))?;
+    let mut de = Deserializer::from_slice(&bytes);
+    de.set_string_mode(StringMode::Utf8OrBytes);
+    let value = serde_json::Value::deserialize(&mut de)?;
+    assert_eq!(value, json!({ "name": "ok" }));
+
+    // Without the flag, strings are always bytes.
+    assert!(serde_bser::de::from_slice::<serde_json::Value>(&bytes).is_err());
+    Ok(())
+}
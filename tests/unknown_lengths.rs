@@ -0,0 +1,129 @@
+use serde::ser::{Serialize, Serializer as _};
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+const TAG_ARRAY: &[u8] = &[0x00];
+const TAG_OBJECT: &[u8] = &[0x01];
+const TAG_STRING: &[u8] = &[0x02];
+const TAG_INT8: &[u8] = &[0x03];
+const TAG_TEMPLATED: &[u8] = &[0x0b];
+const TAG_MISSING: &[u8] = &[0x0c];
+
+/// Serializes the even numbers below `0`, without a length hint.
+struct Evens(i32);
+
+impl Serialize for Evens {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..self.0).filter(|i| i % 2 == 0))
+    }
+}
+
+/// Serializes a map from the even numbers below `0` to their halves, without
+/// a length hint.
+struct Halves(i32);
+
+impl Serialize for Halves {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            (0..self.0)
+                .filter(|i| i % 2 == 0)
+                .map(|i| (i.to_string(), i / 2)),
+        )
+    }
+}
+
+fn to_vec_unknown<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut ser = Serializer::native(Vec::new()).with_unknown_lengths(true);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[test]
+fn strict_by_default() {
+    assert!(matches!(to_vec(&Evens(5)), Err(Error::LengthRequired)));
+    assert!(matches!(to_vec(&Halves(5)), Err(Error::LengthRequired)));
+}
+
+#[test]
+fn buffered_seq() -> Test {
+    let bytes = to_vec_unknown(&Evens(5))?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_ARRAY, TAG_INT8, &[3],
+        TAG_INT8, &[0], TAG_INT8, &[2], TAG_INT8, &[4],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+    assert_eq!(from_slice::<Vec<i32>>(&bytes)?, vec![0, 2, 4]);
+
+    let bytes = to_vec_unknown(&Evens(0))?;
+    assert_eq!(bytes, [TAG_ARRAY, TAG_INT8, &[0]].concat());
+    Ok(())
+}
+
+#[test]
+fn buffered_map() -> Test {
+    let bytes = to_vec_unknown(&Halves(3))?;
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_OBJECT, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"0", TAG_INT8, &[0],
+        TAG_STRING, TAG_INT8, &[1], b"2", TAG_INT8, &[1],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let decoded: BTreeMap<String, i32> = from_slice(&bytes)?;
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded["2"], 1);
+    Ok(())
+}
+
+#[test]
+fn nested_unknown_lengths() -> Test {
+    let value = vec![Evens(3), Evens(7)];
+    let bytes = to_vec_unknown(&value)?;
+    let decoded: Vec<Vec<i32>> = from_slice(&bytes)?;
+    assert_eq!(decoded, vec![vec![0, 2], vec![0, 2, 4, 6]]);
+    Ok(())
+}
+
+#[test]
+fn templated_unknown_lengths() -> Test {
+    struct Rows;
+
+    impl Serialize for Rows {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..4).filter(|i| i % 2 == 1).map(Halves))
+        }
+    }
+
+    let mut ser = Serializer::native(Vec::new())
+        .with_templating(true)
+        .with_unknown_lengths(true);
+    Rows.serialize(&mut ser)?;
+    let bytes = ser.into_inner();
+    #[rustfmt::skip]
+    let expected: Vec<u8> = [
+        TAG_TEMPLATED,
+        TAG_ARRAY, TAG_INT8, &[2],
+        TAG_STRING, TAG_INT8, &[1], b"0",
+        TAG_STRING, TAG_INT8, &[1], b"2",
+        TAG_INT8, &[2],
+        TAG_INT8, &[0], TAG_MISSING,
+        TAG_INT8, &[0], TAG_INT8, &[1],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+
+    let mut ser = Serializer::native(Vec::new()).with_templating(true);
+    assert!(matches!(
+        ser.serialize_seq(None).map(|_| ()),
+        Err(Error::LengthRequired)
+    ));
+    Ok(())
+}
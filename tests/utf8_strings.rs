@@ -0,0 +1,48 @@
+use serde::ser::Serialize;
+use serde_bser::error::Error;
+use serde_bser::ser::{to_vec, Serializer};
+use serde_bser::value::Value;
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+fn to_vec_utf8<T: Serialize>(value: &T, templating: bool) -> Result<Vec<u8>, Error> {
+    let mut ser = Serializer::native(Vec::new())
+        .with_require_utf8_strings(true)
+        .with_templating(templating);
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+#[test]
+fn valid_strings_are_unchanged() -> Test {
+    let value = ("text", 'c', Value::String(b"bytes".to_vec()));
+    assert_eq!(to_vec_utf8(&value, false)?, to_vec(&value)?);
+    Ok(())
+}
+
+#[test]
+fn invalid_bytes_are_rejected() -> Test {
+    let invalid = Value::String(b"a\xff".to_vec());
+    // Accepted unless UTF-8 is required.
+    to_vec(&invalid)?;
+
+    let err = to_vec_utf8(&invalid, false).unwrap_err();
+    assert_eq!(err, Error::NonUtf8String);
+    assert!(err.is_data());
+    assert_eq!(err.to_string(), "string is not valid UTF-8");
+
+    // Keys, and the fields of templated rows, are checked too.
+    let mut object = BTreeMap::new();
+    object.insert(b"a\xff".to_vec(), Value::Null);
+    assert_eq!(
+        to_vec_utf8(&Value::Object(object), false),
+        Err(Error::NonUtf8String)
+    );
+
+    let mut row = BTreeMap::new();
+    row.insert(b"name".to_vec(), invalid);
+    let rows = vec![Value::Object(row.clone()), Value::Object(row)];
+    assert_eq!(to_vec_utf8(&rows, true), Err(Error::NonUtf8String));
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use serde::de::{Deserialize, IntoDeserializer};
+use serde_bser::de::from_slice;
+use serde_bser::error::Error;
+use serde_bser::ser::to_vec;
+use serde_bser::value::{F32Bits, Value};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+
+type Test = Result<(), Box<dyn std::error::Error>>;
+
+fn sample() -> Value {
+    let mut file = BTreeMap::new();
+    file.insert(b"name".to_vec(), Value::String(b"a.txt".to_vec()));
+    file.insert(b"size".to_vec(), Value::Integer(1976));
+    file.insert(b"exists".to_vec(), Value::Bool(true));
+
+    let mut root = BTreeMap::new();
+    root.insert(b"clock".to_vec(), Value::String(b"c:123".to_vec()));
+    root.insert(b"files".to_vec(), Value::Array(vec![Value::Object(file)]));
+    root.insert(b"ratio".to_vec(), Value::Real(0.5));
+    root.insert(b"warning".to_vec(), Value::Null);
+    Value::Object(root)
+}
+
+#[test]
+fn value_round_trip() -> Test {
+    let value = sample();
+    let bytes = to_vec(&value)?;
+    let decoded: Value = from_slice(&bytes)?;
+    assert_eq!(decoded, value);
+    Ok(())
+}
+
+#[test]
+fn value_accessors() {
+    let value = sample();
+    assert_eq!(value.get("clock").and_then(Value::as_str), Some("c:123"));
+    assert_eq!(value.get("ratio").and_then(Value::as_f64), Some(0.5));
+    assert!(value.get("warning").is_some_and(Value::is_null));
+    assert!(value.get("missing").is_none());
+
+    let files = value.get("files").and_then(Value::as_array).unwrap();
+    assert_eq!(files[0].get("size").and_then(Value::as_i64), Some(1976));
+    assert_eq!(files[0].get(b"exists").and_then(Value::as_bool), Some(true));
+}
+
+#[test]
+fn value_non_utf8_string() -> Test {
+    let value = Value::String(vec![0xff, 0xfe]);
+    let decoded: Value = from_slice(&to_vec(&value)?)?;
+    assert_eq!(decoded.as_bytes(), Some(&[0xff, 0xfe][..]));
+    assert_eq!(decoded.as_str(), None);
+    Ok(())
+}
+
+#[test]
+fn value_from_templated() -> Test {
+    #[rustfmt::skip]
+    let bytes: Vec<u8> = [
+        &[0x0b][..],
+        &[0x00, 0x03, 0x02],
+        &[0x02, 0x03, 0x01], b"a",
+        &[0x02, 0x03, 0x01], b"b",
+        &[0x03, 0x02],
+        &[0x03, 0x01], &[0x0c],
+        &[0x03, 0x02], &[0x03, 0x03],
+    ]
+    .concat();
+
+    let decoded: Value = from_slice(&bytes)?;
+    let rows = decoded.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get("a"), Some(&Value::Integer(1)));
+    assert_eq!(rows[0].get("b"), None);
+    assert_eq!(rows[1].get("b"), Some(&Value::Integer(3)));
+    Ok(())
+}
+
+#[test]
+fn f32_bits() -> Test {
+    let values = [
+        1.1f32,
+        -0.0,
+        f32::MIN_POSITIVE / 4.0,
+        f32::from_bits(1),
+        f32::INFINITY,
+        f32::NAN,
+        f32::from_bits(0x7fc0_1234),
+        f32::from_bits(0xff80_0001),
+    ];
+    for &v in &values {
+        let decoded: F32Bits = from_slice(&to_vec(&F32Bits(v))?)?;
+        assert_eq!(decoded.0.to_bits(), v.to_bits(), "{:?}", v);
+    }
+
+    // A plain `f32` is widened, but still reads back as the same `f32`.
+    let bytes = to_vec(&1.1f32)?;
+    assert_eq!(from_slice::<f64>(&bytes)?, 1.1f32 as f64);
+    assert_eq!(from_slice::<f32>(&bytes)?.to_bits(), 1.1f32.to_bits());
+    let subnormal = f32::from_bits(1);
+    assert_eq!(from_slice::<f32>(&to_vec(&subnormal)?)?.to_bits(), 1);
+    Ok(())
+}
+
+#[test]
+fn pointer() {
+    let mut value = sample();
+    if let Value::Object(root) = &mut value {
+        root.insert(b"a/b".to_vec(), Value::Integer(1));
+        root.insert(b"m~n".to_vec(), Value::Integer(2));
+    }
+
+    assert_eq!(value.pointer(""), Some(&value));
+    assert_eq!(
+        value.pointer("/files/0/name"),
+        Some(&Value::String(b"a.txt".to_vec()))
+    );
+    assert_eq!(value.pointer("/a~1b"), Some(&Value::Integer(1)));
+    assert_eq!(value.pointer("/m~0n"), Some(&Value::Integer(2)));
+
+    // Missing keys, indices out of range, malformed indices, segments into
+    // scalars and pointers without a leading slash find nothing.
+    for pointer in &[
+        "/nope",
+        "/files/1",
+        "/files/00",
+        "/files/+0",
+        "/files/x",
+        "/clock/0",
+        "files",
+    ] {
+        assert_eq!(value.pointer(pointer), None, "{}", pointer);
+    }
+
+    *value.pointer_mut("/files/0/size").unwrap() = Value::Integer(7);
+    assert_eq!(value.pointer("/files/0/size"), Some(&Value::Integer(7)));
+    assert!(value.pointer_mut("/files/1").is_none());
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct File<'a> {
+    name: &'a str,
+    size: u32,
+    exists: bool,
+    kind: Option<Kind>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Kind {
+    File,
+    Link { target: String },
+}
+
+#[test]
+fn deserialize_from_value() -> Test {
+    let value: Value = from_slice(&to_vec(&sample())?)?;
+
+    // Strings are borrowed from a `&Value`.
+    let files = value.get("files").unwrap();
+    let decoded = Vec::<File>::deserialize(files)?;
+    let expected = File {
+        name: "a.txt",
+        size: 1976,
+        exists: true,
+        kind: None,
+    };
+    assert_eq!(decoded, [expected]);
+    assert_eq!(f64::deserialize(value.get("ratio").unwrap())?, 0.5);
+
+    // An owned `Value` is consumed.
+    let mut value = value;
+    let file = value.pointer_mut("/files/0").unwrap();
+    if let Value::Object(file) = file {
+        let mut link = BTreeMap::new();
+        link.insert(b"target".to_vec(), Value::String(b"b.txt".to_vec()));
+        let mut kind = BTreeMap::new();
+        kind.insert(b"Link".to_vec(), Value::Object(link));
+        file.insert(b"kind".to_vec(), Value::Object(kind));
+    }
+    let files = value.get("files").unwrap().clone();
+    let decoded = Vec::<BTreeMap<String, Value>>::deserialize(files.into_deserializer())?;
+    let kind = decoded[0]["kind"].clone();
+    assert_eq!(
+        Kind::deserialize(kind)?,
+        Kind::Link {
+            target: "b.txt".to_owned()
+        }
+    );
+    let unit = Value::String(b"File".to_vec());
+    assert_eq!(Kind::deserialize(unit)?, Kind::File);
+
+    // The value round-trips through itself.
+    assert_eq!(Value::deserialize(&value)?, value);
+    assert_eq!(Value::deserialize(value.clone())?, value);
+
+    // Strings need to be valid UTF-8 only where text is expected.
+    let invalid = Value::String(vec![0xff]);
+    assert!(matches!(
+        String::deserialize(&invalid),
+        Err(Error::Utf8Error(_))
+    ));
+    assert_eq!(serde_bytes::ByteBuf::deserialize(invalid)?, [0xff]);
+
+    let err = i32::deserialize(&Value::Bool(true)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: boolean `true`, expected i32"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_conversions() -> Test {
+    use serde_json::json;
+    use std::convert::TryFrom;
+
+    let expected = json!({
+        "clock": "c:123",
+        "files": [{ "exists": true, "name": "a.txt", "size": 1976 }],
+        "ratio": 0.5,
+        "warning": null,
+    });
+    assert_eq!(sample().try_to_json()?, expected);
+    assert_eq!(serde_json::Value::try_from(sample())?, expected);
+    assert_eq!(Value::try_from(expected)?, sample());
+
+    // Invalid UTF-8, in strings or keys, is only accepted lossily.
+    let mut object = BTreeMap::new();
+    object.insert(b"a\xff".to_vec(), Value::String(b"b\xff".to_vec()));
+    let invalid = Value::Array(vec![Value::Object(object)]);
+    assert_eq!(invalid.try_to_json(), Err(Error::NonUtf8String));
+    assert_eq!(
+        invalid.to_json_lossy()?,
+        json!([{ "a\u{fffd}": "b\u{fffd}" }])
+    );
+
+    // JSON can't hold every float, and BSER can't hold every integer.
+    for &f in &[f64::NAN, f64::INFINITY] {
+        assert_eq!(Value::Real(f).to_json_lossy(), Err(Error::NonFiniteFloat));
+    }
+    assert_eq!(
+        Value::try_from(json!(u64::MAX)),
+        Err(Error::IntegerOverflow)
+    );
+    assert_eq!(Value::try_from(json!(-1.5))?, Value::Real(-1.5));
+    Ok(())
+}